@@ -1,13 +1,13 @@
-use std::collections::HashMap;
 use std::str::FromStr;
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
+use common::vm::{Flow, InstructionSet, Machine, Word};
 use common::{default_puzzle, BadInput, Puzzle};
 
 type Register = char;
-type Offset = i32;
+type Offset = Word;
 
 lazy_static! {
     static ref HLF_RE: Regex = Regex::new(r"^hlf (a|b)$").unwrap();
@@ -32,7 +32,7 @@ fn parse_offset(captures: Option<regex::Match>) -> Offset {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Instruction {
     Hlf(Register),
     Tpl(Register),
@@ -77,87 +77,38 @@ impl FromStr for Instruction {
     }
 }
 
-pub struct Computer {
-    registers: HashMap<Register, i32>,
-}
-
-impl Computer {
-    fn new() -> Computer {
-        let mut registers = HashMap::new();
-        registers.insert('a', 0);
-        registers.insert('b', 0);
-        Computer { registers }
-    }
-
-    fn get_register(&self, register: char) -> i32 {
-        *self.registers.get(&register).unwrap_or(&0)
-    }
-
-    fn half(&mut self, register: Register) {
-        let current_value = self.get_register(register);
-        self.registers.insert(register, current_value / 2);
-    }
-
-    fn triple(&mut self, register: Register) {
-        let current_value = self.get_register(register);
-        self.registers.insert(register, current_value * 3);
-    }
-
-    fn increment(&mut self, register: Register) {
-        let current_value = self.get_register(register);
-        self.registers.insert(register, current_value + 1);
-    }
-
-    fn process(&mut self, instructions: &[Instruction]) {
-        let mut pos: i32 = 0;
-        let max_pos: i32 = instructions.len() as i32 - 1;
-        loop {
-            let instruction = instructions[pos as usize].clone();
-            pos += 1;
-            match instruction {
-                Instruction::Hlf(reg) => self.half(reg),
-                Instruction::Tpl(reg) => self.triple(reg),
-                Instruction::Inc(reg) => self.increment(reg),
-                Instruction::Jmp(offset) => {
-                    pos += offset - 1; // -1 to negate += 1 at top
-                }
-                Instruction::Jie(reg, offset) => {
-                    let reg_val = self.get_register(reg);
-                    if reg_val % 2 == 0 {
-                        pos += offset - 1; // -1 to negate += 1 at top
-                    }
-                }
-                Instruction::Jio(reg, offset) => {
-                    let reg_val = self.get_register(reg);
-                    if reg_val == 1 {
-                        pos += offset - 1; // -1 to negate += 1 at top
-                    }
-                }
-            }
-            if pos < 0 {
-                panic!("Was told to jump before the first instruction.");
-            }
-            if pos > max_pos {
-                break;
+impl InstructionSet for Instruction {
+    fn execute(&self, machine: &mut Machine<Self>) -> Flow {
+        match *self {
+            Instruction::Hlf(reg) => machine.set(reg, machine.get(reg) / 2),
+            Instruction::Tpl(reg) => machine.set(reg, machine.get(reg) * 3),
+            Instruction::Inc(reg) => machine.set(reg, machine.get(reg) + 1),
+            Instruction::Jmp(offset) => return Flow::Jump(offset),
+            Instruction::Jie(reg, offset) if machine.get(reg) % 2 == 0 => {
+                return Flow::Jump(offset)
             }
+            Instruction::Jio(reg, offset) if machine.get(reg) == 1 => return Flow::Jump(offset),
+            Instruction::Jie(..) | Instruction::Jio(..) => {}
         }
+        Flow::Next
     }
 }
 
+// Runs the program with register a starting at `a`, returning the final value of b.
+fn run(instructions: Vec<Instruction>, a: Word) -> Word {
+    let mut machine = Machine::new(instructions, "ab");
+    machine.set('a', a);
+    machine.run();
+    machine.get('b')
+}
+
 fn get_puzzle() -> Puzzle {
     let mut puzzle = default_puzzle!("Opening the Turing Lock");
     puzzle.set_part1("final value in register b (a=0)", |reader| {
-        let instructions = reader.parsed_lines();
-        let mut computer = Computer::new();
-        computer.process(&instructions);
-        computer.get_register('b') as u64
+        run(reader.parsed_lines(), 0) as u64
     });
     puzzle.set_part2("final value in register b (a=1)", |reader| {
-        let instructions = reader.parsed_lines();
-        let mut computer = Computer::new();
-        computer.increment('a');
-        computer.process(&instructions);
-        computer.get_register('b') as u64
+        run(reader.parsed_lines(), 1) as u64
     });
     puzzle
 }
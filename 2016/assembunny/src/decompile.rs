@@ -0,0 +1,266 @@
+use std::collections::BTreeSet;
+
+use crate::optimize::{find_shortcuts, Shortcut};
+use crate::{Instruction, Int, Value};
+
+const INDENT: &str = "    ";
+
+struct Decompiler<'a> {
+    instructions: &'a [Instruction],
+    shortcuts: Vec<Option<Shortcut>>,
+    // Each line of output with the address of the instruction it starts at.
+    lines: Vec<(usize, String)>,
+    gotos: BTreeSet<usize>,
+}
+
+// `+=` or `-=`, for a coefficient of 1 or -1.
+fn accumulate(sign: Int) -> &'static str {
+    if sign > 0 {
+        "+="
+    } else {
+        "-="
+    }
+}
+
+impl<'a> Decompiler<'a> {
+    fn emit(&mut self, pos: usize, depth: usize, text: String) {
+        self.lines
+            .push((pos, format!("{}{}", INDENT.repeat(depth), text)));
+    }
+
+    // The target of a `jnz` with a constant offset, if it stays inside the program.
+    fn target(&self, pos: usize, offset: Value) -> Option<usize> {
+        match offset {
+            Value::Integer(offset) => usize::try_from(pos as Int + offset)
+                .ok()
+                .filter(|&target| target <= self.instructions.len()),
+            Value::Register(_) => None,
+        }
+    }
+
+    // The last jump in `pos + 1..end` that jumps back to `pos`.
+    fn loop_end(&self, pos: usize, end: usize) -> Option<usize> {
+        (pos + 1..end).rev().find(|&j| match self.instructions[j] {
+            Instruction::Jnz(cond, offset) => {
+                cond != Value::Integer(0) && self.target(j, offset) == Some(pos)
+            }
+            _ => false,
+        })
+    }
+
+    // Decompiles the instructions in `start..end`, recovering loops and conditionals where
+    // the jumps nest properly and falling back to `goto` where they don't.
+    fn block(&mut self, start: usize, end: usize, depth: usize) {
+        let mut pos = start;
+        while pos < end {
+            if let Some(len) = self.shortcut(pos, end, depth) {
+                pos += len;
+                continue;
+            }
+            if let Some(j) = self.loop_end(pos, end) {
+                let cond = match self.instructions[j] {
+                    Instruction::Jnz(Value::Register(reg), _) => Some(reg),
+                    _ => None,
+                };
+                self.emit(
+                    pos,
+                    depth,
+                    if cond.is_some() { "do {" } else { "loop {" }.into(),
+                );
+                self.block(pos, j, depth + 1);
+                let close = match cond {
+                    Some(reg) => format!("}} while {} != 0", reg),
+                    None => "}".to_string(),
+                };
+                self.emit(pos, depth, close);
+                pos = j + 1;
+                continue;
+            }
+            pos = self.statement(pos, end, depth);
+        }
+    }
+
+    fn shortcut(&mut self, pos: usize, end: usize, depth: usize) -> Option<usize> {
+        match self.shortcuts[pos]? {
+            Shortcut::Add {
+                target,
+                target_delta,
+                counter,
+                counter_delta,
+            } if pos + 3 <= end => {
+                let op = accumulate(-target_delta * counter_delta);
+                self.emit(pos, depth, format!("{} {} {}", target, op, counter));
+                self.emit(pos, depth, format!("{} = 0", counter));
+                Some(3)
+            }
+            Shortcut::Multiply {
+                source,
+                target,
+                target_delta,
+                counter,
+                counter_delta,
+                outer,
+                outer_delta,
+            } if pos + 6 <= end => {
+                let op = accumulate(target_delta * counter_delta * outer_delta);
+                let text = format!("{} {} {} * {}", target, op, source, outer);
+                self.emit(pos, depth, text);
+                self.emit(pos, depth, format!("{} = 0", counter));
+                self.emit(pos, depth, format!("{} = 0", outer));
+                Some(6)
+            }
+            _ => None,
+        }
+    }
+
+    // Decompiles the single statement at `pos`, returning where the next one starts.
+    fn statement(&mut self, pos: usize, end: usize, depth: usize) -> usize {
+        use Instruction::*;
+        let text = match self.instructions[pos] {
+            Cpy(from, Value::Register(reg)) => format!("{} = {}", reg, from),
+            Inc(Value::Register(reg)) => format!("{} += 1", reg),
+            Dec(Value::Register(reg)) => format!("{} -= 1", reg),
+            Tgl(val) => format!("toggle({} + {})", pos, val),
+            Out(val) => format!("output({})", val),
+            Jnz(Value::Integer(0), _) => "nop".to_string(),
+            Jnz(cond, offset) => return self.jump(pos, end, depth, cond, offset),
+            instruction => format!("nop  // {}", instruction),
+        };
+        self.emit(pos, depth, text);
+        pos + 1
+    }
+
+    fn jump(&mut self, pos: usize, end: usize, depth: usize, cond: Value, offset: Value) -> usize {
+        let target = self.target(pos, offset);
+        if let (Value::Register(reg), Some(target)) = (cond, target) {
+            // `jnz x 2` followed by `jnz 1 n` only runs what follows when x is nonzero.
+            let skip = match self.instructions.get(pos + 1) {
+                Some(&Instruction::Jnz(Value::Integer(c), offset))
+                    if c != 0 && target == pos + 2 =>
+                {
+                    self.target(pos + 1, offset)
+                }
+                _ => None,
+            };
+            if let Some(skip) = skip.filter(|&skip| skip > pos + 2 && skip <= end) {
+                self.emit(pos, depth, format!("if {} != 0 {{", reg));
+                self.block(pos + 2, skip, depth + 1);
+                self.emit(pos, depth, "}".into());
+                return skip;
+            }
+            if target > pos + 1 && target <= end {
+                self.emit(pos, depth, format!("if {} == 0 {{", reg));
+                self.block(pos + 1, target, depth + 1);
+                self.emit(pos, depth, "}".into());
+                return target;
+            }
+        }
+        let goto = match offset {
+            Value::Integer(offset) => {
+                self.gotos.extend(target);
+                format!("goto {}", pos as Int + offset)
+            }
+            Value::Register(reg) => format!("goto {} + {}", pos, reg),
+        };
+        match cond {
+            Value::Register(reg) => {
+                self.emit(pos, depth, format!("if {} != 0 {{", reg));
+                self.emit(pos, depth + 1, goto);
+                self.emit(pos, depth, "}".into());
+            }
+            Value::Integer(_) => self.emit(pos, depth, goto),
+        }
+        pos + 1
+    }
+}
+
+// Turns a program into pseudo-code, with loops that only add or multiply collapsed into
+// arithmetic and other jumps recovered as loops and conditionals where possible. Addresses
+// reached by a leftover `goto` are labelled.
+pub fn decompile(instructions: &[Instruction]) -> String {
+    let mut decompiler = Decompiler {
+        instructions,
+        shortcuts: find_shortcuts(instructions),
+        lines: vec![],
+        gotos: BTreeSet::new(),
+    };
+    decompiler.block(0, instructions.len(), 0);
+    let mut out = String::new();
+    let mut labelled = BTreeSet::new();
+    for (pos, line) in &decompiler.lines {
+        if decompiler.gotos.contains(pos) && labelled.insert(*pos) {
+            out.push_str(&format!("{}:\n", pos));
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    if decompiler.gotos.contains(&instructions.len()) {
+        out.push_str(&format!("{}:\n", instructions.len()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::InputReader;
+
+    fn parse(source: &str) -> Vec<Instruction> {
+        source.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let program = parse("cpy 41 a\ninc a\ninc a\ndec a\njnz a 2\ndec a\ntgl c\nout a");
+        assert_eq!(
+            decompile(&program),
+            "a = 41\na += 1\na += 1\na -= 1\nif a == 0 {\n    a -= 1\n}\ntoggle(6 + c)\noutput(a)\n"
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        let program = parse("cpy 3 b\ncpy 2 c\ninc a\ndec c\njnz c -2\ndec b\njnz b -5\njnz 1 -7");
+        assert_eq!(
+            decompile(&program),
+            "loop {\n    b = 3\n    a += 2 * b\n    c = 0\n    b = 0\n}\n"
+        );
+        let program = parse("dec a\nout a\njnz a -2\nout b");
+        assert_eq!(
+            decompile(&program),
+            "do {\n    a -= 1\n    output(a)\n} while a != 0\noutput(b)\n"
+        );
+    }
+
+    #[test]
+    fn test_gotos() {
+        let program = parse("jnz a 2\njnz 1 3\ninc b\ninc b\njnz 1 c\njnz a 9");
+        assert_eq!(
+            decompile(&program),
+            "if a != 0 {\n    b += 1\n    b += 1\n}\ngoto 4 + c\nif a != 0 {\n    goto 14\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_day_12() {
+        let program: Vec<Instruction> = InputReader::new("../day-12/input.txt").parsed_lines();
+        let code = decompile(&program);
+        // The fibonacci loop.
+        assert!(code.contains(
+            "do {\n    c = a\n    a += b\n    b = 0\n    b = c\n    d -= 1\n} while d != 0\n"
+        ));
+        assert!(!code.contains("goto"));
+    }
+
+    #[test]
+    fn test_day_25() {
+        let program: Vec<Instruction> = InputReader::new("../day-25/input.txt").parsed_lines();
+        let code = decompile(&program);
+        assert!(
+            code.starts_with("d = a\nc = 7\nd += 365 * c\nb = 0\nc = 0\n"),
+            "{}",
+            code
+        );
+        assert!(code.contains("\n20:\n"));
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use common::vm::{Flow, InstructionSet, Machine};
+use common::BadInput;
+
+use crate::optimize::Shortcut;
+
+mod decompile;
+mod optimize;
+
+pub use crate::decompile::decompile;
+pub use common::vm::{State, Step};
+
+pub type Int = common::vm::Word;
+
+// The registers every 2016 assembunny program uses.
+pub const DEFAULT_REGISTERS: &str = "abcd";
+
+lazy_static! {
+    static ref INSTRUCTION_RE: Regex =
+        Regex::new(r"^([a-z]{3}) (\S+)(?: (\S+))?$").expect("Bad instruction regex.");
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Value {
+    Integer(Int),
+    Register(char),
+}
+
+impl FromStr for Value {
+    type Err = BadInput;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(reg), None) if reg.is_ascii_lowercase() => Ok(Value::Register(reg)),
+            _ => text.parse().map(Value::Integer).map_err(|_| BadInput),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(num) => write!(f, "{}", num),
+            Value::Register(reg) => write!(f, "{}", reg),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Instruction {
+    Cpy(Value, Value),
+    Inc(Value),
+    Dec(Value),
+    Jnz(Value, Value),
+    Tgl(Value),
+    Out(Value),
+}
+
+impl Instruction {
+    // What `tgl` turns this instruction into.
+    pub fn toggled(self) -> Instruction {
+        use Instruction::*;
+        match self {
+            Cpy(val1, val2) => Jnz(val1, val2),
+            Jnz(val1, val2) => Cpy(val1, val2),
+            Inc(val) => Dec(val),
+            Dec(val) | Tgl(val) | Out(val) => Inc(val),
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = BadInput;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let caps = INSTRUCTION_RE.captures(line.trim()).ok_or(BadInput)?;
+        let val1: Value = caps[2].parse()?;
+        let val2: Option<Value> = caps.get(3).map(|m| m.as_str().parse()).transpose()?;
+        match (&caps[1], val2) {
+            ("cpy", Some(val2)) => Ok(Instruction::Cpy(val1, val2)),
+            ("jnz", Some(val2)) => Ok(Instruction::Jnz(val1, val2)),
+            ("inc", None) => Ok(Instruction::Inc(val1)),
+            ("dec", None) => Ok(Instruction::Dec(val1)),
+            ("tgl", None) => Ok(Instruction::Tgl(val1)),
+            ("out", None) => Ok(Instruction::Out(val1)),
+            _ => Err(BadInput),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Cpy(val1, val2) => write!(f, "cpy {} {}", val1, val2),
+            Instruction::Jnz(val1, val2) => write!(f, "jnz {} {}", val1, val2),
+            Instruction::Inc(val) => write!(f, "inc {}", val),
+            Instruction::Dec(val) => write!(f, "dec {}", val),
+            Instruction::Tgl(val) => write!(f, "tgl {}", val),
+            Instruction::Out(val) => write!(f, "out {}", val),
+        }
+    }
+}
+
+impl InstructionSet for Instruction {
+    // Instructions made nonsensical by `tgl` (like copying into an integer) are skipped.
+    fn execute(&self, machine: &mut Machine<Self>) -> Flow {
+        use Instruction::*;
+        let num = |machine: &Machine<Self>, value| match value {
+            Value::Integer(num) => num,
+            Value::Register(reg) => machine.get(reg),
+        };
+        match *self {
+            Cpy(from, Value::Register(reg)) => machine.set(reg, num(machine, from)),
+            Inc(Value::Register(reg)) => machine.set(reg, machine.get(reg) + 1),
+            Dec(Value::Register(reg)) => machine.set(reg, machine.get(reg) - 1),
+            Jnz(val1, val2) => {
+                if num(machine, val1) != 0 {
+                    return Flow::Jump(num(machine, val2));
+                }
+            }
+            Tgl(val) => {
+                let target = machine.pos() + num(machine, val);
+                if let Some(instruction) = usize::try_from(target)
+                    .ok()
+                    .and_then(|target| machine.program_mut().get_mut(target))
+                {
+                    *instruction = instruction.toggled();
+                }
+            }
+            Out(val) => return Flow::Output(num(machine, val)),
+            Cpy(_, Value::Integer(_)) | Inc(Value::Integer(_)) | Dec(Value::Integer(_)) => {}
+        }
+        Flow::Next
+    }
+}
+
+// An assembunny machine, optionally with loops replaced by arithmetic (see `optimize`).
+#[derive(Clone, Debug)]
+pub struct Computer {
+    machine: Machine<Instruction>,
+    shortcuts: Option<Vec<Option<Shortcut>>>,
+}
+
+impl Computer {
+    pub fn new(instructions: Vec<Instruction>) -> Computer {
+        Computer::with_registers(instructions, DEFAULT_REGISTERS)
+    }
+
+    // A computer with one register per character of `names`, all starting at zero.
+    pub fn with_registers(instructions: Vec<Instruction>, names: &str) -> Computer {
+        Computer {
+            machine: Machine::new(instructions, names),
+            shortcuts: None,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<Instruction> {
+        &self.machine
+    }
+
+    pub fn get_register(&self, register: char) -> Int {
+        self.machine.get(register)
+    }
+
+    pub fn set_register(&mut self, register: char, value: Int) {
+        self.machine.set(register, value);
+    }
+
+    pub fn registers(&self) -> &[Int] {
+        self.machine.registers().values()
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        self.machine.program()
+    }
+
+    pub fn pos(&self) -> Int {
+        self.machine.pos()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.machine.steps()
+    }
+
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.machine.set_step_limit(limit);
+    }
+
+    pub fn state(&self) -> State<Instruction> {
+        self.machine.state()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    fn get_num(&self, value: Value) -> Int {
+        match value {
+            Value::Integer(num) => num,
+            Value::Register(reg) => self.get_register(reg),
+        }
+    }
+
+    pub fn step(&mut self) -> Step {
+        if self.is_halted() {
+            return Step::Halted;
+        }
+        if self.take_shortcut() {
+            return Step::Ran;
+        }
+        let toggles = matches!(self.machine.current(), Some(Instruction::Tgl(_)));
+        let result = self.machine.step();
+        if toggles {
+            self.refresh_shortcuts();
+        }
+        result
+    }
+
+    // Runs until the program halts (or the step limit is reached).
+    pub fn run(&mut self) {
+        while let Step::Ran | Step::Output(_) = self.step() {}
+    }
+
+    // Runs until the next `out`, returning None if the program stops first.
+    pub fn run_until_output(&mut self) -> Option<Int> {
+        loop {
+            match self.step() {
+                Step::Ran => {}
+                Step::Output(value) => return Some(value),
+                Step::Halted | Step::OutOfSteps => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Instruction> {
+        source.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        use Instruction::*;
+        use Value::*;
+        assert_eq!(
+            "cpy 41 a".parse::<Instruction>().unwrap(),
+            Cpy(Integer(41), Register('a'))
+        );
+        assert_eq!(
+            "jnz c -2".parse::<Instruction>().unwrap(),
+            Jnz(Register('c'), Integer(-2))
+        );
+        assert_eq!("tgl a".parse::<Instruction>().unwrap(), Tgl(Register('a')));
+        assert_eq!("out b".parse::<Instruction>().unwrap(), Out(Register('b')));
+        assert!("inc a b".parse::<Instruction>().is_err());
+        assert!("cpy a".parse::<Instruction>().is_err());
+        assert!("mul a b".parse::<Instruction>().is_err());
+        assert!("inc ab".parse::<Instruction>().is_err());
+        for line in ["cpy 41 a", "jnz c -2", "tgl a", "out b"] {
+            assert_eq!(line.parse::<Instruction>().unwrap().to_string(), line);
+        }
+    }
+
+    #[test]
+    fn test_day_12_example() {
+        let mut computer = Computer::new(parse("cpy 41 a\ninc a\ninc a\ndec a\njnz a 2\ndec a"));
+        computer.run();
+        assert_eq!(computer.get_register('a'), 42);
+        assert_eq!(computer.steps(), 5);
+    }
+
+    #[test]
+    fn test_day_23_example() {
+        let mut computer =
+            Computer::new(parse("cpy 2 a\ntgl a\ntgl a\ntgl a\ncpy 1 a\ndec a\ndec a"));
+        computer.run();
+        assert_eq!(computer.get_register('a'), 3);
+        assert_eq!(computer.instructions()[3], "inc a".parse().unwrap());
+    }
+
+    #[test]
+    fn test_output_and_registers() {
+        let mut computer = Computer::with_registers(parse("cpy 3 x\nout x\ndec x\njnz x -2"), "x");
+        let outputs: Vec<Int> = std::iter::from_fn(|| computer.run_until_output()).collect();
+        assert_eq!(outputs, vec![3, 2, 1]);
+        assert!(computer.is_halted());
+        assert_eq!(computer.registers(), &[0]);
+    }
+
+    #[test]
+    fn test_state() {
+        let mut computer = Computer::new(parse("inc a\ndec a\njnz 1 -2"));
+        let start = computer.state();
+        computer.step();
+        assert_ne!(computer.state(), start);
+        computer.step();
+        computer.step();
+        assert_eq!(computer.state(), start);
+    }
+}
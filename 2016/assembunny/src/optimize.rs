@@ -0,0 +1,301 @@
+use crate::{Computer, Instruction, Int, Value};
+
+// A loop recognised in the program that can be replaced by arithmetic.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Shortcut {
+    // `inc/dec target`, `inc/dec counter`, `jnz counter -2` (in either order): moves the
+    // counter to zero, adding to or subtracting from the target on the way.
+    Add {
+        target: char,
+        target_delta: Int,
+        counter: char,
+        counter_delta: Int,
+    },
+    // `cpy source counter`, an add loop on that counter, then `inc/dec outer` and
+    // `jnz outer -5`: the add loop runs `outer` times.
+    Multiply {
+        source: Value,
+        target: char,
+        target_delta: Int,
+        counter: char,
+        counter_delta: Int,
+        outer: char,
+        outer_delta: Int,
+    },
+}
+
+// `inc reg` or `dec reg` as (reg, +1 or -1).
+fn unit_change(instruction: Instruction) -> Option<(char, Int)> {
+    match instruction {
+        Instruction::Inc(Value::Register(reg)) => Some((reg, 1)),
+        Instruction::Dec(Value::Register(reg)) => Some((reg, -1)),
+        _ => None,
+    }
+}
+
+fn add_loop(window: &[Instruction]) -> Option<Shortcut> {
+    let (first, second) = (unit_change(window[0])?, unit_change(window[1])?);
+    let counter = match window[2] {
+        Instruction::Jnz(Value::Register(reg), Value::Integer(-2)) => reg,
+        _ => return None,
+    };
+    let ((target, target_delta), (_, counter_delta)) = if second.0 == counter {
+        (first, second)
+    } else if first.0 == counter {
+        (second, first)
+    } else {
+        return None;
+    };
+    if target == counter {
+        return None;
+    }
+    Some(Shortcut::Add {
+        target,
+        target_delta,
+        counter,
+        counter_delta,
+    })
+}
+
+fn multiply_loop(window: &[Instruction]) -> Option<Shortcut> {
+    let source = match window[0] {
+        Instruction::Cpy(source, Value::Register(_)) => source,
+        _ => return None,
+    };
+    let (target, target_delta, counter, counter_delta) = match add_loop(&window[1..4])? {
+        Shortcut::Add {
+            target,
+            target_delta,
+            counter,
+            counter_delta,
+        } => (target, target_delta, counter, counter_delta),
+        _ => return None,
+    };
+    let (outer, outer_delta) = unit_change(window[4])?;
+    let jumps_back = window[5] == Instruction::Jnz(Value::Register(outer), Value::Integer(-5));
+    let copies_counter = window[0] == Instruction::Cpy(source, Value::Register(counter));
+    let distinct = outer != target
+        && outer != counter
+        && [target, counter, outer]
+            .iter()
+            .all(|&reg| source != Value::Register(reg));
+    if !jumps_back || !copies_counter || !distinct {
+        return None;
+    }
+    Some(Shortcut::Multiply {
+        source,
+        target,
+        target_delta,
+        counter,
+        counter_delta,
+        outer,
+        outer_delta,
+    })
+}
+
+// The shortcut (if any) starting at each instruction.
+pub(crate) fn find_shortcuts(instructions: &[Instruction]) -> Vec<Option<Shortcut>> {
+    (0..instructions.len())
+        .map(|pos| {
+            let rest = &instructions[pos..];
+            let multiply = rest.get(..6).and_then(multiply_loop);
+            multiply.or_else(|| rest.get(..3).and_then(add_loop))
+        })
+        .collect()
+}
+
+// How many times a loop stepping its counter by `delta` runs before the counter hits zero,
+// or None if it never would.
+fn iterations(counter: Int, delta: Int) -> Option<Int> {
+    let n = -counter * delta;
+    if n > 0 {
+        Some(n)
+    } else {
+        None
+    }
+}
+
+impl Computer {
+    // Runs loops that are really additions and multiplications in one go. `tgl` can rewrite
+    // them, so the loops are found again every time it does.
+    pub fn optimize(&mut self) {
+        self.shortcuts = Some(find_shortcuts(self.machine.program()));
+    }
+
+    pub fn is_optimized(&self) -> bool {
+        self.shortcuts.is_some()
+    }
+
+    pub(crate) fn refresh_shortcuts(&mut self) {
+        if self.shortcuts.is_some() {
+            self.optimize();
+        }
+    }
+
+    // Whether a shortcut taking `steps` steps finishes within the step limit. If it wouldn't,
+    // the loop has to run the slow way so it stops at exactly the right instruction.
+    fn within_step_limit(&self, steps: u64) -> bool {
+        self.machine
+            .remaining_steps()
+            .is_none_or(|remaining| steps <= remaining)
+    }
+
+    // Applies the shortcut starting at the current instruction, if there is one and the
+    // loop would finish (within the step limit, if any). The registers, position and step
+    // count end up exactly as if the loop had run.
+    pub(crate) fn take_shortcut(&mut self) -> bool {
+        let shortcut = match &self.shortcuts {
+            Some(shortcuts) => match shortcuts[self.pos() as usize] {
+                Some(shortcut) => shortcut,
+                None => return false,
+            },
+            None => return false,
+        };
+        match shortcut {
+            Shortcut::Add {
+                target,
+                target_delta,
+                counter,
+                counter_delta,
+            } => {
+                let n = match iterations(self.get_register(counter), counter_delta) {
+                    Some(n) => n,
+                    None => return false,
+                };
+                let steps = 3 * n as u64;
+                if !self.within_step_limit(steps) {
+                    return false;
+                }
+                self.set_register(target, self.get_register(target) + target_delta * n);
+                self.set_register(counter, 0);
+                self.machine.advance(3, steps);
+            }
+            Shortcut::Multiply {
+                source,
+                target,
+                target_delta,
+                counter,
+                counter_delta,
+                outer,
+                outer_delta,
+            } => {
+                let inner = iterations(self.get_num(source), counter_delta);
+                let outer_n = iterations(self.get_register(outer), outer_delta);
+                let (inner, outer_n) = match (inner, outer_n) {
+                    (Some(inner), Some(outer_n)) => (inner, outer_n),
+                    _ => return false,
+                };
+                let steps = (outer_n * (3 * inner + 3)) as u64;
+                if !self.within_step_limit(steps) {
+                    return false;
+                }
+                let total = target_delta * inner * outer_n;
+                self.set_register(target, self.get_register(target) + total);
+                self.set_register(counter, 0);
+                self.set_register(outer, 0);
+                self.machine.advance(6, steps);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Step;
+    use common::InputReader;
+
+    fn parse(source: &str) -> Vec<Instruction> {
+        source.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    // Runs with and without shortcuts and checks both end in the same state.
+    fn assert_same(instructions: Vec<Instruction>, a: Int) -> Computer {
+        let mut plain = Computer::new(instructions.clone());
+        let mut optimized = Computer::new(instructions);
+        plain.set_register('a', a);
+        optimized.set_register('a', a);
+        optimized.optimize();
+        plain.run();
+        optimized.run();
+        assert_eq!(plain.registers(), optimized.registers());
+        assert_eq!(plain.steps(), optimized.steps());
+        assert_eq!(plain.instructions(), optimized.instructions());
+        optimized
+    }
+
+    #[test]
+    fn test_find_shortcuts() {
+        let instructions = parse(
+            "cpy b c\ninc a\ndec c\njnz c -2\ndec d\njnz d -5\ndec d\ninc c\njnz d -2\ninc a\ninc a\njnz a -2",
+        );
+        let shortcuts = find_shortcuts(&instructions);
+        assert!(matches!(
+            shortcuts[0],
+            Some(Shortcut::Multiply { target: 'a', .. })
+        ));
+        assert!(matches!(
+            shortcuts[1],
+            Some(Shortcut::Add { target: 'a', .. })
+        ));
+        assert!(matches!(
+            shortcuts[6],
+            Some(Shortcut::Add {
+                target: 'c',
+                counter: 'd',
+                ..
+            })
+        ));
+        // A loop can't use its own counter as the target.
+        assert_eq!(shortcuts[9], None);
+        assert_eq!(shortcuts.iter().flatten().count(), 3);
+    }
+
+    #[test]
+    fn test_multiply() {
+        let source = "cpy 4 d\ncpy 7 b\ncpy b c\ninc a\ndec c\njnz c -2\ndec d\njnz d -5";
+        let computer = assert_same(parse(source), 3);
+        assert_eq!(computer.get_register('a'), 3 + 28);
+        // A negative counter counting up is fine too.
+        let computer = assert_same(parse("cpy -5 d\ndec a\ninc d\njnz d -2"), 1);
+        assert_eq!(computer.get_register('a'), -4);
+    }
+
+    #[test]
+    fn test_step_limit_inside_shortcut() {
+        let source = "cpy 4 d\ncpy 7 b\ncpy b c\ninc a\ndec c\njnz c -2\ndec d\njnz d -5";
+        // Stops partway through the multiply loop, and then partway through the add loop
+        // inside it once there's no longer room for the whole multiply.
+        for limit in [10, 40, 90] {
+            let mut plain = Computer::new(parse(source));
+            let mut optimized = Computer::new(parse(source));
+            optimized.optimize();
+            plain.set_step_limit(Some(limit));
+            optimized.set_step_limit(Some(limit));
+            plain.run();
+            optimized.run();
+            assert_eq!(optimized.steps(), limit);
+            assert_eq!(optimized.step(), Step::OutOfSteps);
+            assert_eq!(plain.registers(), optimized.registers());
+            assert_eq!(plain.pos(), optimized.pos());
+        }
+    }
+
+    #[test]
+    fn test_toggle_invalidates() {
+        // The add loop is found up front, then `tgl` turns its `inc a` into `dec a`.
+        let source = "cpy 3 c\ncpy 2 b\ntgl b\ncpy 3 c\ninc a\ndec c\njnz c -2";
+        let computer = assert_same(parse(source), 0);
+        assert_eq!(computer.get_register('a'), -3);
+        assert_eq!(computer.instructions()[4], "dec a".parse().unwrap());
+    }
+
+    #[test]
+    fn test_puzzle_inputs() {
+        let day12: Vec<Instruction> = InputReader::new("../day-12/input.txt").parsed_lines();
+        assert_eq!(assert_same(day12, 0).get_register('a'), 318009);
+        let day23: Vec<Instruction> = InputReader::new("../day-23/input.txt").parsed_lines();
+        assert_eq!(assert_same(day23, 7).get_register('a'), 11340);
+    }
+}
@@ -1,18 +1,27 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::env;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::iter;
+use std::str::FromStr;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub enum Isotope {
-    Pm, // Promethium,
-    Pu, // Plutonium,
-    Ru, // Ruthenium,
-    Sr, // Strontium,
-    Tm, // Thulium
-    Elerium,
-    Dilithium,
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use common::{BadInput, InputReader};
+
+lazy_static! {
+    static ref FLOOR_RE: Regex =
+        Regex::new(r"^The (\w+) floor contains (.*)\.$").expect("Bad floor regex.");
+    static ref ITEM_RE: Regex =
+        Regex::new(r"an? ([a-z]+)(-compatible)? (generator|microchip)").expect("Bad item regex.");
 }
 
+// An element, as an index into the building's list of element names.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Isotope(usize);
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Item {
     Generator(Isotope),
@@ -59,9 +68,139 @@ impl Floor {
     }
 }
 
+// What matters about a building when searching for the fewest trips: where the elevator
+// is, and the floors of each element's generator and microchip (in that order). Insight
+// from https://andars.github.io/aoc_day11.html: which element is which doesn't matter,
+// so the pairs are kept sorted. Items that aren't in the building are on floor `height`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct State {
+    elevator: usize,
+    pairs: Vec<(usize, usize)>,
+}
+
+impl State {
+    fn is_safe(&self, height: usize) -> bool {
+        self.pairs.iter().all(|&(generator, microchip)| {
+            generator == microchip
+                || microchip == height
+                || self.pairs.iter().all(|&(other, _)| other != microchip)
+        })
+    }
+
+    fn is_done(&self, height: usize) -> bool {
+        self.pairs
+            .iter()
+            .all(|&(generator, microchip)| generator >= height - 1 && microchip >= height - 1)
+    }
+
+    // A lower bound on the trips left, for A*. Every trip crosses one gap between floors.
+    // To get `n` items up across a gap, taking at most two up and bringing at least one
+    // back down each round, the elevator crosses it at least 2n - 3 times (and at least
+    // once) if it starts below the gap, or 2n times if it starts above.
+    fn trips_left(&self, height: usize) -> u32 {
+        let mut below = 0;
+        let mut trips = 0;
+        for floor in 0..height - 1 {
+            for &(generator, microchip) in self.pairs.iter() {
+                below += (generator == floor) as u32 + (microchip == floor) as u32;
+            }
+            if below == 0 {
+                continue;
+            }
+            trips += if self.elevator <= floor {
+                (2 * below).saturating_sub(3).max(1)
+            } else {
+                2 * below
+            };
+        }
+        trips
+    }
+
+    fn next_states(&self, height: usize) -> Vec<State> {
+        // Each item on the elevator's floor, as (pair, whether it's the microchip).
+        let mut items = Vec::new();
+        for (i, &(generator, microchip)) in self.pairs.iter().enumerate() {
+            if generator == self.elevator {
+                items.push((i, false));
+            }
+            if microchip == self.elevator {
+                items.push((i, true));
+            }
+        }
+        let up = Some(self.elevator + 1).filter(|&floor| floor < height);
+        let down = self.elevator.checked_sub(1);
+        let mut states = Vec::new();
+        for to in up.into_iter().chain(down) {
+            for (i, &item1) in items.iter().enumerate() {
+                for item2 in iter::once(None).chain(items[i + 1..].iter().copied().map(Some)) {
+                    let mut next = State {
+                        elevator: to,
+                        pairs: self.pairs.clone(),
+                    };
+                    for (pair, is_microchip) in iter::once(item1).chain(item2) {
+                        if is_microchip {
+                            next.pairs[pair].1 = to;
+                        } else {
+                            next.pairs[pair].0 = to;
+                        }
+                    }
+                    if next.is_safe(height) {
+                        next.pairs.sort_unstable();
+                        states.push(next);
+                    }
+                }
+            }
+        }
+        states
+    }
+}
+
+// Packs states into integers: one base `height + 1` digit for the elevator, then two per
+// pair.
+struct Codec {
+    base: u64,
+    n_pairs: usize,
+}
+
+impl Codec {
+    fn new(height: usize, n_pairs: usize) -> Codec {
+        let base = height as u64 + 1;
+        assert!(
+            base.checked_pow(2 * n_pairs as u32 + 1).is_some(),
+            "Too many items to pack the building's state into 64 bits."
+        );
+        Codec { base, n_pairs }
+    }
+
+    fn encode(&self, state: &State) -> u64 {
+        state
+            .pairs
+            .iter()
+            .fold(state.elevator as u64, |code, &(generator, microchip)| {
+                (code * self.base + generator as u64) * self.base + microchip as u64
+            })
+    }
+
+    fn decode(&self, mut code: u64) -> State {
+        let mut pairs = vec![(0, 0); self.n_pairs];
+        for pair in pairs.iter_mut().rev() {
+            let microchip = code % self.base;
+            code /= self.base;
+            let generator = code % self.base;
+            code /= self.base;
+            *pair = (generator as usize, microchip as usize);
+        }
+        State {
+            elevator: code as usize,
+            pairs,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Building {
     floors: Vec<Floor>,
+    elements: Vec<String>,
     height: usize,
     elevator: usize,
     n_moves: u32,
@@ -69,50 +208,94 @@ pub struct Building {
 
 impl PartialEq for Building {
     fn eq(&self, other: &Building) -> bool {
-        self.get_iso_pairs() == other.get_iso_pairs()
-            && self.height == other.height
-            && self.elevator == other.elevator
+        self.state() == other.state() && self.height == other.height
     }
 }
 impl Eq for Building {}
 
 impl Hash for Building {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.get_iso_pairs().hash(state);
+        self.state().hash(state);
         self.height.hash(state);
-        self.elevator.hash(state);
         // exclude n_moves from the Hash
     }
 }
 
+// A two letter symbol for an element, e.g. "Th" for thulium.
+fn symbol(name: &str) -> String {
+    let mut chars = name.chars();
+    let first = chars.next().map(|c| c.to_ascii_uppercase());
+    first.into_iter().chain(chars.next()).collect()
+}
+
 impl fmt::Display for Building {
+    // Draws the floors top to bottom like the puzzle does, with a column for each item.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "+----------------------------+").unwrap();
-        writeln!(f, "| Fn  E  Pm  Pu  Ru  Sr  Tm  |").unwrap();
-        writeln!(f, "+----------------------------+").unwrap();
-        let mut reversed_floors = self.floors.clone();
-        reversed_floors.reverse();
-        for (i, floor) in reversed_floors.iter().enumerate() {
-            write!(f, "|  {}", self.height - i).unwrap();
-            if self.elevator == self.height - i - 1 {
-                write!(f, "  x").unwrap();
-            } else {
-                write!(f, "   ").unwrap();
+        for (i, floor) in self.floors.iter().enumerate().rev() {
+            let elevator = if self.elevator == i { "E" } else { "." };
+            let mut row = format!("F{} {}  ", i + 1, elevator);
+            for (n, name) in self.elements.iter().enumerate() {
+                let isotope = Isotope(n);
+                for (items, kind) in [(&floor.generators, 'G'), (&floor.microchips, 'M')] {
+                    let label = if items.contains(&isotope) {
+                        format!("{}{}", symbol(name), kind)
+                    } else {
+                        ".".to_string()
+                    };
+                    row.push_str(&format!("{:<4}", label));
+                }
             }
-            for _ in floor.generators.iter() {
-                write!(f, "  .").unwrap();
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Building {
+    type Err = BadInput;
+
+    // One "The first floor contains ..." line per floor, from the bottom up. Every element
+    // needs exactly one generator and one microchip.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        use Item::*;
+        let mut elements: Vec<String> = Vec::new();
+        let mut items: Vec<Vec<Item>> = Vec::new();
+        let mut seen = HashSet::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let caps = FLOOR_RE.captures(line).ok_or(BadInput)?;
+            let mut floor_items = Vec::new();
+            for item in ITEM_RE.captures_iter(&caps[2]) {
+                let n = match elements.iter().position(|name| name == &item[1]) {
+                    Some(n) => n,
+                    None => {
+                        elements.push(item[1].to_string());
+                        elements.len() - 1
+                    }
+                };
+                let item = match (item.get(2).is_some(), &item[3]) {
+                    (false, "generator") => Generator(Isotope(n)),
+                    (true, "microchip") => Microchip(Isotope(n)),
+                    _ => return Err(BadInput),
+                };
+                if !seen.insert(item) {
+                    return Err(BadInput);
+                }
+                floor_items.push(item);
             }
-            for _ in floor.microchips.iter() {
-                write!(f, "  .").unwrap();
+            if floor_items.is_empty() && &caps[2] != "nothing relevant" {
+                return Err(BadInput);
             }
-            writeln!(f).unwrap();
+            items.push(floor_items);
         }
-        writeln!(f, "+----------------------------+")
+        if items.is_empty() || seen.len() != 2 * elements.len() {
+            return Err(BadInput);
+        }
+        Ok(Building::with_items(elements, items))
     }
 }
 
 impl Building {
-    fn with_items(items: Vec<Vec<Item>>) -> Building {
+    fn with_items(elements: Vec<String>, items: Vec<Vec<Item>>) -> Building {
         let mut floors = Vec::new();
         let height = items.len();
         for floor_items in items {
@@ -120,12 +303,21 @@ impl Building {
         }
         Building {
             floors,
+            elements,
             height,
             elevator: 0,
             n_moves: 0,
         }
     }
 
+    // Adds a generator and a microchip for a new element to `floor`.
+    pub fn add_element(&mut self, name: &str, floor: usize) {
+        let isotope = Isotope(self.elements.len());
+        self.elements.push(name.to_string());
+        self.floors[floor].generators.insert(isotope);
+        self.floors[floor].microchips.insert(isotope);
+    }
+
     fn n_moves(&self) -> u32 {
         self.n_moves
     }
@@ -174,26 +366,17 @@ impl Building {
         None
     }
 
-    fn get_iso_pairs(&self) -> Vec<Vec<usize>> {
-        use Isotope::*;
+    fn state(&self) -> State {
         use Item::*;
-        // Insight from https://andars.github.io/aoc_day11.html: it's only the
-        // locations of the pairs of the generators and microchips that
-        // matters, not the particulars of which isotope is where. So, add them
-        // to the building hash as pairs.
-        let mut iso_pairs: Vec<Vec<usize>> = Vec::new();
-        for iso in &[Pm, Pu, Ru, Sr, Tm, Elerium, Dilithium] {
-            let generator_floor = self.find_item(Generator(*iso));
-            let microchip_floor = self.find_item(Microchip(*iso));
-            if generator_floor.is_some() && microchip_floor.is_some() {
-                let mut pair_floors: Vec<usize> =
-                    vec![generator_floor.unwrap(), microchip_floor.unwrap()];
-                pair_floors.sort_unstable();
-                iso_pairs.push(pair_floors);
-            }
+        let floor = |item| self.find_item(item).unwrap_or(self.height);
+        let mut pairs: Vec<(usize, usize)> = (0..self.elements.len())
+            .map(|n| (floor(Generator(Isotope(n))), floor(Microchip(Isotope(n)))))
+            .collect();
+        pairs.sort_unstable();
+        State {
+            elevator: self.elevator,
+            pairs,
         }
-        iso_pairs.sort();
-        iso_pairs
     }
 
     fn clone_and_move(&self, item1: Option<Item>, item2: Option<Item>, to: usize) -> Building {
@@ -241,142 +424,121 @@ impl Building {
         if self.elevator_can_go_down() {
             possible_floors.push(self.elevator - 1);
         }
-        {
-            let mut add_state = |item1, item2| {
-                for f in possible_floors.iter() {
-                    let possibility = self.clone_and_move(item1, item2, *f);
+        let items: Vec<Item> = curr_floor
+            .generators
+            .iter()
+            .map(|iso| Generator(*iso))
+            .chain(curr_floor.microchips.iter().map(|iso| Microchip(*iso)))
+            .collect();
+        for f in possible_floors.iter() {
+            for (i, item) in items.iter().enumerate() {
+                for item2 in iter::once(None).chain(items[i + 1..].iter().copied().map(Some)) {
+                    let possibility = self.clone_and_move(Some(*item), item2, *f);
                     if possibility.is_safe_for_microchips() {
                         states.push(possibility);
                     }
                 }
-            };
-            let gens: Vec<_> = curr_floor.generators.clone().into_iter().collect();
-            let chps: Vec<_> = curr_floor.microchips.clone().into_iter().collect();
-            for (i, iso) in gens.iter().enumerate() {
-                let item = Some(Generator(*iso));
-                add_state(item, None);
-                if chps.contains(iso) {
-                    add_state(item, Some(Microchip(*iso)));
-                }
-                for gen in gens.iter().skip(i + 1) {
-                    let item2 = Some(Generator(*gen));
-                    add_state(item, item2);
-                }
-            }
-            for (i, iso) in chps.iter().enumerate() {
-                let item = Some(Microchip(*iso));
-                add_state(item, None);
-                for chp in chps.iter().skip(i + 1) {
-                    let item2 = Some(Microchip(*chp));
-                    add_state(item, item2);
-                }
             }
         }
         states
     }
 }
 
-pub fn get_example_building() -> Building {
-    use Isotope::*;
-    use Item::*;
-    // From input.txt:
-    Building::with_items(vec![
-        vec![Microchip(Tm), Microchip(Pu)],
-        vec![Generator(Tm)],
-        vec![Generator(Pu)],
-        vec![],
-    ])
+pub fn read_building(path: &'static str) -> Building {
+    let lines: Vec<String> = InputReader::new(path).parsed_lines();
+    lines.join("\n").parse().unwrap()
 }
 
-pub fn get_initial_building() -> Building {
-    use Isotope::*;
-    use Item::*;
-    // From input.txt:
-    Building::with_items(vec![
-        vec![Generator(Tm), Microchip(Tm), Generator(Pu), Generator(Sr)],
-        vec![Microchip(Pu), Microchip(Sr)],
-        vec![Generator(Pm), Microchip(Pm), Generator(Ru), Microchip(Ru)],
-        vec![],
-    ])
+// Finds a shortest sequence of elevator trips that gets everything to the top floor, as the
+// building after each trip (starting with `building` itself), or None if it can't be done.
+// The A* search only keeps packed states around; once it finds the way, the trips are
+// replayed on the real building to see which items moved.
+pub fn plan_elevator_trips(building: &Building) -> Option<Vec<Building>> {
+    let height = building.height;
+    let start = building.state();
+    let codec = Codec::new(height, start.pairs.len());
+    let start_code = codec.encode(&start);
+    // the fewest trips found to each state, and the state before it
+    let mut best: HashMap<u64, (u32, Option<u64>)> = HashMap::new();
+    best.insert(start_code, (0, None));
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse((start.trips_left(height), 0, start_code)));
+    let mut goal = None;
+    while let Some(Reverse((_, trips, code))) = queue.pop() {
+        if best[&code].0 < trips {
+            continue;
+        }
+        let state = codec.decode(code);
+        if state.is_done(height) {
+            goal = Some(code);
+            break;
+        }
+        for next in state.next_states(height) {
+            let next_code = codec.encode(&next);
+            if best
+                .get(&next_code)
+                .is_some_and(|&(best_trips, _)| best_trips <= trips + 1)
+            {
+                continue;
+            }
+            best.insert(next_code, (trips + 1, Some(code)));
+            queue.push(Reverse((
+                trips + 1 + next.trips_left(height),
+                trips + 1,
+                next_code,
+            )));
+        }
+    }
+    let mut codes = vec![goal?];
+    while let Some(previous) = best[codes.last().unwrap()].1 {
+        codes.push(previous);
+    }
+    codes.reverse();
+    let mut buildings = vec![building.clone()];
+    for code in &codes[1..] {
+        let next = buildings
+            .last()
+            .unwrap()
+            .get_next_states()
+            .into_iter()
+            .find(|next| codec.encode(&next.state()) == *code)
+            .expect("Planned trip isn't possible in the building.");
+        buildings.push(next);
+    }
+    Some(buildings)
 }
 
-pub fn get_initial_building_for_part_two() -> Building {
-    use Isotope::*;
-    use Item::*;
-    // From input.txt:
-    Building::with_items(vec![
-        vec![
-            Generator(Tm),
-            Microchip(Tm),
-            Generator(Pu),
-            Generator(Sr),
-            Generator(Elerium),
-            Microchip(Elerium),
-            Generator(Dilithium),
-            Microchip(Dilithium),
-        ],
-        vec![Microchip(Pu), Microchip(Sr)],
-        vec![Generator(Pm), Microchip(Pm), Generator(Ru), Microchip(Ru)],
-        vec![],
-    ])
+pub fn minimize_elevator_trips(building: Building) -> Option<u32> {
+    plan_elevator_trips(&building).map(|buildings| buildings.last().unwrap().n_moves())
 }
 
-pub fn minimize_elevator_trips(building: Building) -> u32 {
-    if building.has_everything_on_top_floor() {
-        return 0;
-    }
-    let max_queue = 3_000_000;
-    let mut bq: VecDeque<Building> = VecDeque::new();
-    let mut unique_buildings: HashMap<Building, u32> = HashMap::new();
-    let next_states = building.get_next_states();
-    for bldg in next_states {
-        bq.push_back(bldg);
-    }
-    while !bq.is_empty() {
-        if bq.len() > max_queue {
-            println!("Bailing out with {} moves left to process.", max_queue);
-            break;
-        }
-        let new_building = bq.pop_front().unwrap();
-        if bq.len() % 250000 < 5 {
-            println!(
-                "There are {} moves to process; working on move #{}.",
-                bq.len(),
-                new_building.n_moves()
-            );
-        }
-        if !unique_buildings.contains_key(&new_building) {
-            unique_buildings.insert(new_building.clone(), new_building.n_moves());
-        }
-        if new_building.has_everything_on_top_floor() {
-            // doing BFS, so this is the answer
-            return new_building.n_moves();
-        } else {
-            let next_states = new_building.get_next_states();
-            for bldg in next_states {
-                if unique_buildings.get(&bldg).is_some() {
-                    continue;
+// Prints the number of trips, or every step of the way with `--show`.
+fn report(part: u32, building: &Building, show: bool) {
+    match plan_elevator_trips(building) {
+        Some(buildings) => {
+            if show {
+                for (i, building) in buildings.iter().enumerate() {
+                    println!("Trip {}:\n{}", i, building);
                 }
-                unique_buildings.insert(bldg.clone(), bldg.n_moves());
-                bq.push_back(bldg);
             }
+            println!(
+                "Part {}: takes at least {} moves",
+                part,
+                buildings.len() - 1
+            );
         }
+        None => println!("Part {}: can't be done", part),
     }
-    u32::max_value()
 }
 
 fn main() {
-    //    // the example building
-    //    let building = get_example_building();
-    //    let num_moves = minimize_elevator_trips(building);
-    // my building
-    let building = get_initial_building();
-    let num_moves = minimize_elevator_trips(building);
-    println!("Part 1: takes at least {} moves", num_moves);
-    // my building, part 2
-    let building = get_initial_building_for_part_two();
-    let num_moves = minimize_elevator_trips(building);
-    println!("Part 2: takes at least {} moves", num_moves);
+    let show = env::args().any(|arg| arg == "--show");
+    let mut building = read_building("input.txt");
+    report(1, &building, show);
+    // part 2 finds two more pairs on the first floor
+    building.add_element("elerium", 0);
+    building.add_element("dilithium", 0);
+    report(2, &building, show);
 }
 
 #[cfg(test)]
@@ -385,15 +547,28 @@ mod tests {
 
     use super::*;
 
+    const EXAMPLE: &str = "The first floor contains a hydrogen-compatible microchip and a lithium-compatible microchip.
+The second floor contains a hydrogen generator.
+The third floor contains a lithium generator.
+The fourth floor contains nothing relevant.";
+
+    const PM: Isotope = Isotope(0);
+    const PU: Isotope = Isotope(1);
+    const SR: Isotope = Isotope(2);
+    const TM: Isotope = Isotope(3);
+
+    fn building(items: Vec<Vec<Item>>) -> Building {
+        let elements = ["promethium", "plutonium", "strontium", "thulium"];
+        Building::with_items(elements.iter().map(|e| e.to_string()).collect(), items)
+    }
+
     fn get_simple_building() -> Building {
-        use Isotope::*;
         use Item::*;
-        Building::with_items(vec![vec![Generator(Pu), Microchip(Pu)], vec![]])
+        building(vec![vec![Generator(PU), Microchip(PU)], vec![]])
     }
 
     #[test]
     fn test_building_methods() {
-        use Isotope::*;
         use Item::*;
         // make a simple building
         let building = get_simple_building();
@@ -417,7 +592,7 @@ mod tests {
         assert!(!building.elevator_can_go_down());
         assert!(building.elevator_can_go_up());
         // move generator up
-        let building = building.clone_and_move(Some(Generator(Pu)), None, 1);
+        let building = building.clone_and_move(Some(Generator(PU)), None, 1);
         assert_eq!(building.n_moves(), 3);
         assert!(building.is_safe_for_microchips());
         assert!(!building.has_everything_on_top_floor());
@@ -431,7 +606,7 @@ mod tests {
         assert!(!building.elevator_can_go_down());
         assert!(building.elevator_can_go_up());
         // move microchip up
-        let building = building.clone_and_move(Some(Microchip(Pu)), None, 1);
+        let building = building.clone_and_move(Some(Microchip(PU)), None, 1);
         assert_eq!(building.n_moves(), 5);
         assert!(building.is_safe_for_microchips());
         assert!(building.has_everything_on_top_floor());
@@ -441,152 +616,254 @@ mod tests {
 
     #[test]
     fn test_building_hash() {
-        use Isotope::*;
         use Item::*;
         let mut set = HashSet::new();
-        let building1 = Building::with_items(vec![vec![Generator(Pu), Microchip(Pu)], vec![]]);
+        let building1 = building(vec![vec![Generator(PU), Microchip(PU)], vec![]]);
         set.insert(building1);
-        let building2 = Building::with_items(vec![vec![Microchip(Pu), Generator(Pu)], vec![]]);
+        let building2 = building(vec![vec![Microchip(PU), Generator(PU)], vec![]]);
         assert!(set.contains(&building2));
-        let building3 = Building::with_items(vec![vec![], vec![Microchip(Pu), Generator(Pu)]]);
+        let building3 = building(vec![vec![], vec![Microchip(PU), Generator(PU)]]);
         assert!(!set.contains(&building3));
     }
 
     #[test]
     fn test_building_hash_just_cares_about_pairs() {
-        use Isotope::*;
         use Item::*;
         let mut set = HashSet::new();
-        let building1 = Building::with_items(vec![vec![Generator(Pu)], vec![Microchip(Pu)]]);
+        let building1 = building(vec![vec![Generator(PU)], vec![Microchip(PU)]]);
         set.insert(building1);
-        let building2 = Building::with_items(vec![vec![Microchip(Pu)], vec![Generator(Pu)]]);
-        assert!(set.contains(&building2));
-        let building3 = Building::with_items(vec![vec![Microchip(Tm)], vec![Generator(Tm)]]);
+        // which of the pair is where still matters
+        let building2 = building(vec![vec![Microchip(PU)], vec![Generator(PU)]]);
+        assert!(!set.contains(&building2));
+        set.insert(building2);
+        let building3 = building(vec![vec![Microchip(TM)], vec![Generator(TM)]]);
         assert!(set.contains(&building3));
-        let building4 = Building::with_items(vec![vec![Generator(Tm)], vec![Microchip(Tm)]]);
+        let building4 = building(vec![vec![Generator(TM)], vec![Microchip(TM)]]);
         assert!(set.contains(&building4));
     }
 
     #[test]
     fn test_building_hash_just_cares_about_pairs_with_two_isotopes() {
-        use Isotope::*;
         use Item::*;
         let mut set = HashSet::new();
-        let building1 = Building::with_items(vec![
-            vec![Generator(Pu), Generator(Tm)],
-            vec![Microchip(Pu), Microchip(Tm)],
+        let building1 = building(vec![
+            vec![Generator(PU), Generator(TM)],
+            vec![Microchip(PU), Microchip(TM)],
         ]);
         set.insert(building1);
-        let building2 = Building::with_items(vec![
-            vec![Microchip(Pu), Microchip(Tm)],
-            vec![Generator(Pu), Generator(Tm)],
+        let building2 = building(vec![
+            vec![Microchip(PU), Microchip(TM)],
+            vec![Generator(PU), Generator(TM)],
         ]);
-        assert!(set.contains(&building2));
-        let building3 = Building::with_items(vec![
-            vec![Microchip(Pm), Microchip(Sr)],
-            vec![Generator(Pm), Generator(Sr)],
+        assert!(!set.contains(&building2));
+        set.insert(building2);
+        let building3 = building(vec![
+            vec![Microchip(PM), Microchip(SR)],
+            vec![Generator(PM), Generator(SR)],
         ]);
         assert!(set.contains(&building3));
     }
 
+    #[test]
+    fn test_codec() {
+        let building: Building = EXAMPLE.parse().unwrap();
+        let state = building.state();
+        assert_eq!(
+            state,
+            State {
+                elevator: 0,
+                pairs: vec![(1, 0), (2, 0)]
+            }
+        );
+        let codec = Codec::new(4, 2);
+        // digits 0, 1, 0, 2, 0 in base 5
+        assert_eq!(codec.encode(&state), 125 + 2 * 5);
+        assert_eq!(codec.decode(codec.encode(&state)), state);
+        let state = building.clone_and_move(None, None, 3).state();
+        assert_eq!(codec.decode(codec.encode(&state)), state);
+    }
+
+    #[test]
+    fn test_next_states() {
+        let building: Building = EXAMPLE.parse().unwrap();
+        // the only safe move is taking the hydrogen microchip up to its generator
+        let next = building.state().next_states(4);
+        assert_eq!(
+            next,
+            vec![State {
+                elevator: 1,
+                pairs: vec![(1, 1), (2, 0)]
+            }]
+        );
+        assert!(building.state().trips_left(4) <= 11);
+    }
+
+    #[test]
+    fn test_plan_elevator_trips() {
+        use Item::*;
+        let example: Building = EXAMPLE.parse().unwrap();
+        let buildings = plan_elevator_trips(&example).unwrap();
+        assert_eq!(buildings.len(), 12);
+        assert_eq!(buildings[0], example);
+        for (i, pair) in buildings.windows(2).enumerate() {
+            assert_eq!(pair[1].n_moves(), i as u32 + 1);
+            assert!(pair[0].get_next_states().contains(&pair[1]));
+        }
+        assert!(buildings[11].has_everything_on_top_floor());
+        assert_eq!(
+            buildings[11].to_string(),
+            "F4 E  HyG HyM LiG LiM\nF3 .  .   .   .   .\nF2 .  .   .   .   .\nF1 .  .   .   .   .\n"
+        );
+        // a microchip stuck under a generator it can't get past
+        let stuck = building(vec![
+            vec![Microchip(PU)],
+            vec![Generator(TM)],
+            vec![Generator(PU)],
+        ]);
+        assert_eq!(plan_elevator_trips(&stuck), None);
+    }
+
     #[test]
     fn test_building_hash_with_moves() {
-        use Isotope::*;
         use Item::*;
         let mut set = HashSet::new();
-        let building1 = Building::with_items(vec![vec![Generator(Pu), Microchip(Pu)], vec![]]);
+        let building1 = building(vec![vec![Generator(PU), Microchip(PU)], vec![]]);
         set.insert(building1.clone());
         // move generator up
-        let building2 = building1.clone_and_move(Some(Generator(Pu)), None, 1);
+        let building2 = building1.clone_and_move(Some(Generator(PU)), None, 1);
         assert!(!set.contains(&building2));
         // move generator back down
-        let building3 = building2.clone_and_move(Some(Generator(Pu)), None, 0);
+        let building3 = building2.clone_and_move(Some(Generator(PU)), None, 0);
         assert!(set.contains(&building3));
     }
 
     #[test]
     fn test_single_story_building() {
-        use Isotope::*;
         use Item::*;
-        let building = Building::with_items(vec![vec![Generator(Pu), Microchip(Pu)]]);
+        let building = building(vec![vec![Generator(PU), Microchip(PU)]]);
         assert!(building.has_everything_on_top_floor());
         let num = minimize_elevator_trips(building);
-        assert_eq!(num, 0);
+        assert_eq!(num, Some(0));
     }
 
     #[test]
     fn test_two_story_building_already_done() {
-        use Isotope::*;
         use Item::*;
-        let building = Building::with_items(vec![vec![], vec![Generator(Pu)]]);
+        let building = building(vec![vec![], vec![Generator(PU)]]);
         assert!(building.has_everything_on_top_floor());
         let num = minimize_elevator_trips(building);
-        assert_eq!(num, 0);
+        assert_eq!(num, Some(0));
     }
 
     #[test]
     fn test_two_story_building_with_one_move_to_go() {
-        use Isotope::*;
         use Item::*;
-        let building = Building::with_items(vec![vec![Generator(Pu)], vec![]]);
+        let building = building(vec![vec![Generator(PU)], vec![]]);
         assert!(!building.has_everything_on_top_floor());
         let num = minimize_elevator_trips(building);
-        assert_eq!(num, 1);
+        assert_eq!(num, Some(1));
     }
 
     #[test]
     fn test_two_story_building_with_one_move_to_go_and_two_items() {
-        use Isotope::*;
         use Item::*;
-        let building = Building::with_items(vec![vec![Generator(Pu)], vec![Microchip(Pu)]]);
+        let building = building(vec![vec![Generator(PU)], vec![Microchip(PU)]]);
         assert!(!building.has_everything_on_top_floor());
         let num = minimize_elevator_trips(building);
-        assert_eq!(num, 1);
+        assert_eq!(num, Some(1));
     }
 
     #[test]
     fn test_three_story_building_with_two_moves_to_go() {
-        use Isotope::*;
         use Item::*;
-        let building = Building::with_items(vec![vec![Generator(Pu)], vec![], vec![]]);
+        let building = building(vec![vec![Generator(PU)], vec![], vec![]]);
         assert!(!building.has_everything_on_top_floor());
         let num = minimize_elevator_trips(building);
-        assert_eq!(num, 2);
+        assert_eq!(num, Some(2));
     }
 
     #[test]
     fn test_stupid_simple_building() {
-        use Isotope::*;
         use Item::*;
-        let building = Building::with_items(vec![vec![Generator(Pu), Microchip(Pu)], vec![]]);
+        let building = building(vec![vec![Generator(PU), Microchip(PU)], vec![]]);
         let num_moves = minimize_elevator_trips(building);
-        assert_eq!(num_moves, 1);
+        assert_eq!(num_moves, Some(1));
     }
 
     #[test]
     fn test_example_building() {
-        let building = get_example_building();
+        let building: Building = EXAMPLE.parse().unwrap();
         let num_moves = minimize_elevator_trips(building);
-        assert_eq!(num_moves, 11);
+        assert_eq!(num_moves, Some(11));
+    }
+
+    #[test]
+    fn test_parse() {
+        use Item::*;
+        let building: Building = EXAMPLE.parse().unwrap();
+        let (hydrogen, lithium) = (Isotope(0), Isotope(1));
+        assert_eq!(building.elements, vec!["hydrogen", "lithium"]);
+        assert_eq!(building.height, 4);
+        assert_eq!(building.find_item(Microchip(hydrogen)), Some(0));
+        assert_eq!(building.find_item(Microchip(lithium)), Some(0));
+        assert_eq!(building.find_item(Generator(hydrogen)), Some(1));
+        assert_eq!(building.find_item(Generator(lithium)), Some(2));
+        // any number of floors will do
+        let two_floors = "The first floor contains a lithium generator.
+The second floor contains a lithium-compatible microchip.";
+        assert_eq!(two_floors.parse::<Building>().unwrap().height, 2);
+        // but every element needs both its generator and its microchip
+        assert!("The first floor contains a lithium generator."
+            .parse::<Building>()
+            .is_err());
+        assert!("The first floor contains a lithium-compatible generator."
+            .parse::<Building>()
+            .is_err());
+        assert!(
+            "The first floor contains a hydrogen generator and a hydrogen generator."
+                .parse::<Building>()
+                .is_err()
+        );
+        assert!("The first floor contains everything."
+            .parse::<Building>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let mut building: Building = EXAMPLE.parse().unwrap();
+        assert_eq!(
+            building.to_string(),
+            "F4 .  .   .   .   .\nF3 .  .   .   LiG .\nF2 .  HyG .   .   .\nF1 E  .   HyM .   LiM\n"
+        );
+        building.add_element("elerium", 1);
+        assert!(building
+            .to_string()
+            .starts_with("F4 .  .   .   .   .   .   .\n"));
+        assert!(building
+            .to_string()
+            .contains("F2 .  HyG .   .   .   ElG ElM\n"));
     }
 
     #[test]
     fn test_initial_building_is_safe() {
-        let building = get_initial_building();
+        let building = read_building("input.txt");
         assert!(building.is_safe_for_microchips());
     }
 
     #[test]
     fn test_part_1_answer() {
-        let building = get_initial_building();
+        let building = read_building("input.txt");
         let num_moves = minimize_elevator_trips(building);
-        assert_eq!(num_moves, 31);
+        assert_eq!(num_moves, Some(31));
     }
 
     #[test]
     fn test_part_2_answer() {
-        let building = get_initial_building_for_part_two();
+        let mut building = read_building("input.txt");
+        building.add_element("elerium", 0);
+        building.add_element("dilithium", 0);
         let num_moves = minimize_elevator_trips(building);
-        assert_eq!(num_moves, 55);
+        assert_eq!(num_moves, Some(55));
     }
 }
@@ -1,8 +1,14 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
 
-const SALT: u64 = 1358;
+use common::InputReader;
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+const PATH_COLOUR: &str = "\x1b[1;32m";
+const REACHABLE_COLOUR: &str = "\x1b[44m";
+const RESET_COLOUR: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Point {
     x: u8,
     y: u8,
@@ -13,7 +19,7 @@ impl Point {
         Point { x, y }
     }
 
-    fn get_valid_neighbors(&self) -> Vec<Point> {
+    fn get_neighbors(&self) -> Vec<Point> {
         let mut neighbors = Vec::new();
         if let Some(x) = self.x.checked_sub(1) {
             neighbors.push(Point::new(x, self.y));
@@ -27,52 +33,177 @@ impl Point {
         if let Some(y) = self.y.checked_add(1) {
             neighbors.push(Point::new(self.x, y));
         }
-        neighbors.into_iter().filter(|p| !p.is_wall()).collect()
+        neighbors
+    }
+}
+
+// The cubicle maze, laid out by the office designer's favourite number.
+pub struct Office {
+    favourite: u64,
+}
+
+impl Office {
+    pub fn new(favourite: u64) -> Office {
+        Office { favourite }
     }
 
-    fn is_wall(&self) -> bool {
-        let x = self.x as u64;
-        let y = self.y as u64;
-        let value: u64 = x * x + 3 * x + 2 * x * y + y + y * y + SALT;
+    fn is_wall(&self, point: &Point) -> bool {
+        let x = point.x as u64;
+        let y = point.y as u64;
+        let value: u64 = x * x + 3 * x + 2 * x * y + y + y * y + self.favourite;
         value.count_ones() % 2 == 1
     }
-}
 
-pub fn find_shortest_path(start: &Point, end: &Point) -> u32 {
-    let mut q: VecDeque<(Point, u32)> = VecDeque::new();
-    let mut visited: HashSet<Point> = HashSet::new();
-    for point in start.get_valid_neighbors() {
-        q.push_back((point, 1));
-    }
-    visited.insert(start.clone());
-    let mut part2_printed = false;
-    while !q.is_empty() {
-        let (point, num) = q.pop_front().unwrap();
-        if !part2_printed && num >= 50 {
-            println!("Have seen {} points with under 50 moves.", visited.len());
-            part2_printed = true;
-        }
-        if point == *end {
-            return num;
-        } else {
-            for to_visit in point.get_valid_neighbors() {
-                if !visited.contains(&to_visit) {
-                    q.push_back((to_visit.clone(), num + 1));
-                    visited.insert(to_visit);
+    fn get_valid_neighbors(&self, point: &Point) -> Vec<Point> {
+        let mut neighbors = point.get_neighbors();
+        neighbors.retain(|p| !self.is_wall(p));
+        neighbors
+    }
+
+    // Breadth-first search from `start`, going at most `max_steps` and stopping early if it
+    // gets to `end`. Returns the point each visited point was first reached from (the
+    // start is reached from itself).
+    fn explore(&self, start: &Point, max_steps: u32, end: Option<&Point>) -> HashMap<Point, Point> {
+        let mut q: VecDeque<(Point, u32)> = VecDeque::new();
+        let mut came_from: HashMap<Point, Point> = HashMap::new();
+        q.push_back((*start, 0));
+        came_from.insert(*start, *start);
+        while let Some((point, num)) = q.pop_front() {
+            if Some(&point) == end || num == max_steps {
+                continue;
+            }
+            for to_visit in self.get_valid_neighbors(&point) {
+                if let Entry::Vacant(entry) = came_from.entry(to_visit) {
+                    entry.insert(point);
+                    q.push_back((to_visit, num + 1));
                 }
             }
         }
+        came_from
+    }
+
+    // A shortest walk from `start` to `end`, including both ends.
+    pub fn find_path(&self, start: &Point, end: &Point) -> Option<Vec<Point>> {
+        let came_from = self.explore(start, u32::MAX, Some(end));
+        let mut path = vec![*end];
+        let mut point = came_from.get(end)?;
+        while path.last() != Some(start) {
+            path.push(*point);
+            point = &came_from[point];
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    pub fn find_shortest_path(&self, start: &Point, end: &Point) -> Option<u32> {
+        self.find_path(start, end).map(|path| path.len() as u32 - 1)
+    }
+
+    // Every point that can be reached from `start` in at most `steps` steps.
+    pub fn reachable_within(&self, start: &Point, steps: u32) -> HashSet<Point> {
+        self.explore(start, steps, None).into_keys().collect()
+    }
+
+    // Draws the top left of the maze as the puzzle does, with `#` for walls, `O` for the
+    // path and `o` for the other reachable points.
+    pub fn render(
+        &self,
+        width: u8,
+        height: u8,
+        path: &[Point],
+        reachable: &HashSet<Point>,
+    ) -> String {
+        let mut out = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                let point = Point::new(x, y);
+                out.push(if self.is_wall(&point) {
+                    '#'
+                } else if path.contains(&point) {
+                    'O'
+                } else if reachable.contains(&point) {
+                    'o'
+                } else {
+                    '.'
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+// Colours the path and reachable points in a rendered maze for the terminal.
+pub fn highlight(maze: &str) -> String {
+    let mut out = String::new();
+    for c in maze.chars() {
+        match c {
+            'O' => out.push_str(&format!("{}O{}", PATH_COLOUR, RESET_COLOUR)),
+            'o' => out.push_str(&format!("{} {}", REACHABLE_COLOUR, RESET_COLOUR)),
+            _ => out.push(c),
+        }
     }
-    u32::max_value()
+    out
 }
 
+// Takes an optional target and step count as `x y steps`.
 fn main() {
+    let favourite = InputReader::new("input.txt")
+        .string_line()
+        .trim()
+        .parse()
+        .unwrap();
+    let office = Office::new(favourite);
+    let args: Vec<u32> = env::args()
+        .skip(1)
+        .map(|arg| {
+            arg.parse()
+                .unwrap_or_else(|_| panic!("Expected a non-negative number, got {:?}", arg))
+        })
+        .collect();
+    let coordinate = |index: usize, default: u8| {
+        args.get(index).map_or(default, |&arg| {
+            u8::try_from(arg)
+                .unwrap_or_else(|_| panic!("Coordinates only go up to {}, got {}", u8::MAX, arg))
+        })
+    };
     let start = Point::new(1, 1);
-    let end = Point::new(31, 39);
-    let num_steps = find_shortest_path(&start, &end);
+    let end = Point::new(coordinate(0, 31), coordinate(1, 39));
+    let steps = *args.get(2).unwrap_or(&50);
+    let path = office.find_path(&start, &end).unwrap_or_default();
+    let reachable = office.reachable_within(&start, steps);
+    // Leave a margin around everything visited, as far as coordinates go.
+    let width = reachable
+        .iter()
+        .chain(path.iter())
+        .map(|p| p.x)
+        .max()
+        .unwrap()
+        .saturating_add(3);
+    let height = reachable
+        .iter()
+        .chain(path.iter())
+        .map(|p| p.y)
+        .max()
+        .unwrap()
+        .saturating_add(3);
+    print!(
+        "{}",
+        highlight(&office.render(width, height, &path, &reachable))
+    );
+    match path.len() {
+        0 => println!("Part 1: can't walk to {:?}.", end),
+        len => println!(
+            "Part 1: starting at {:?}, can walk to {:?} in {} steps.",
+            start,
+            end,
+            len - 1
+        ),
+    }
     println!(
-        "Stating at {:?}, can walk to {:?} in {} steps.",
-        &start, &end, num_steps
+        "Part 2: can reach {} locations in at most {} steps.",
+        reachable.len(),
+        steps
     );
 }
 
@@ -80,10 +211,56 @@ fn main() {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_example() {
+        let office = Office::new(10);
+        let start = Point::new(1, 1);
+        let end = Point::new(7, 4);
+        let path = office.find_path(&start, &end).unwrap();
+        assert_eq!(path.len(), 12);
+        assert_eq!(path[0], start);
+        assert_eq!(path[11], end);
+        let reachable = office.reachable_within(&start, 2);
+        assert_eq!(reachable.len(), 5);
+        assert_eq!(
+            office.render(10, 7, &path, &reachable),
+            [
+                "o#.####.##",
+                "oO#..#...#",
+                "#OOO.##...",
+                "###O#.###.",
+                ".##OO#.O#.",
+                "..##OOOO#.",
+                "#...##.###",
+                "",
+            ]
+            .join("\n")
+        );
+        assert_eq!(office.find_path(&start, &Point::new(0, 2)), None);
+    }
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(
+            highlight("#Oo.\n"),
+            format!(
+                "#{}O{}{} {}.\n",
+                PATH_COLOUR, RESET_COLOUR, REACHABLE_COLOUR, RESET_COLOUR
+            )
+        );
+    }
+
     #[test]
     fn test_part_one_answer() {
+        let office = Office::new(1358);
         let start = Point::new(1, 1);
         let end = Point::new(31, 39);
-        assert_eq!(96, find_shortest_path(&start, &end));
+        assert_eq!(Some(96), office.find_shortest_path(&start, &end));
+    }
+
+    #[test]
+    fn test_part_two_answer() {
+        let office = Office::new(1358);
+        assert_eq!(office.reachable_within(&Point::new(1, 1), 50).len(), 141);
     }
 }
@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::thread;
 
 use crypto::digest::Digest;
 use crypto::md5::Md5;
@@ -15,7 +16,9 @@ enum Direction {
 
 #[derive(Clone)]
 struct Maze {
-    passcode: String,
+    // Fed with the passcode already; every room hashes the passcode followed by its path,
+    // so each room starts from a copy of this rather than hashing the passcode again.
+    passcode_hasher: Md5,
     path: String,
     x: i32,
     y: i32,
@@ -25,8 +28,10 @@ struct Maze {
 
 impl Maze {
     fn new(passcode: &str) -> Maze {
+        let mut passcode_hasher = Md5::new();
+        passcode_hasher.input_str(passcode);
         Maze {
-            passcode: passcode.to_string(),
+            passcode_hasher,
             path: String::new(),
             x: 0,
             y: 0,
@@ -42,8 +47,7 @@ impl Maze {
     fn get_open_doors(&self) -> Vec<Direction> {
         use Direction::*;
         let mut open_doors = Vec::new();
-        let mut hasher = Md5::new();
-        hasher.input_str(&self.passcode);
+        let mut hasher = self.passcode_hasher;
         hasher.input_str(&self.path);
         let output = hasher.result_str();
         let mut chars = output.chars();
@@ -108,43 +112,117 @@ pub fn get_shortest_path(hash: &str) -> Option<String> {
     None
 }
 
-pub fn get_longest_path(hash: &str) -> Option<String> {
-    let maze = Maze::new(hash);
-    let mut longest_path: Option<String> = None;
-    let mut q = VecDeque::new();
-    for direction in maze.get_open_doors() {
-        q.push_back(maze.clone_and_move(direction));
+// Every way through the vault.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VaultPaths {
+    // The first of the shortest paths, trying doors in the order up, down, left, right.
+    pub shortest: Option<String>,
+    // The first of the longest paths, in the same order.
+    pub longest: Option<String>,
+    pub count: u64,
+}
+
+// Where a path comes in breadth first order: by length, then by the order of the doors.
+fn bfs_order(path: &str) -> (usize, Vec<Option<usize>>) {
+    (path.len(), path.chars().map(|c| "UDLR".find(c)).collect())
+}
+
+impl VaultPaths {
+    fn consider(&mut self, path: &str) {
+        let comes_before = |other: &str| bfs_order(path) < bfs_order(other);
+        if self.shortest.as_deref().is_none_or(comes_before) {
+            self.shortest = Some(path.to_string());
+        }
+        let longer = |other: &str| {
+            path.len() > other.len() || (path.len() == other.len() && comes_before(other))
+        };
+        if self.longest.as_deref().is_none_or(longer) {
+            self.longest = Some(path.to_string());
+        }
     }
-    while !q.is_empty() {
+
+    fn add(&mut self, path: &str) {
+        self.consider(path);
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: VaultPaths) {
+        for path in other.shortest.iter().chain(other.longest.iter()) {
+            self.consider(path);
+        }
+        self.count += other.count;
+    }
+}
+
+// Explores every path through the vault from `maze` depth first.
+fn explore_from(maze: Maze, paths: &mut VaultPaths) {
+    let mut stack = vec![maze];
+    while let Some(maze) = stack.pop() {
+        if maze.is_complete() {
+            paths.add(&maze.path);
+            continue;
+        }
+        for direction in maze.get_open_doors() {
+            stack.push(maze.clone_and_move(direction));
+        }
+    }
+}
+
+// Finds every path through the vault. The first few rooms are explored breadth first until
+// there's enough work to go around, then the rest is split across `threads` threads.
+pub fn find_all_paths(hash: &str, threads: usize) -> VaultPaths {
+    let threads = threads.max(1);
+    let mut paths = VaultPaths::default();
+    let mut q = VecDeque::from(vec![Maze::new(hash)]);
+    while !q.is_empty() && q.len() < 4 * threads {
         let maze = q.pop_front().unwrap();
         if maze.is_complete() {
-            longest_path = match longest_path {
-                Some(path) => {
-                    if path.len() < maze.path.len() {
-                        Some(maze.path)
-                    } else {
-                        Some(path)
-                    }
-                }
-                None => Some(maze.path),
-            };
+            paths.add(&maze.path);
             continue;
         }
         for direction in maze.get_open_doors() {
             q.push_back(maze.clone_and_move(direction));
         }
     }
-    longest_path
+    let mazes: Vec<Maze> = q.into_iter().collect();
+    let chunk_size = mazes.len().div_ceil(threads).max(1);
+    let found: Vec<VaultPaths> = thread::scope(|scope| {
+        let workers: Vec<_> = mazes
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut paths = VaultPaths::default();
+                    for maze in chunk {
+                        explore_from(maze.clone(), &mut paths);
+                    }
+                    paths
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    for other in found {
+        paths.merge(other);
+    }
+    paths
+}
+
+pub fn get_longest_path(hash: &str) -> Option<String> {
+    find_all_paths(hash, 1).longest
 }
 
 fn main() {
-    let path1 = get_shortest_path(MY_HASH);
-    println!("Part 1: the shortest path is {:?}", path1);
-    let path2 = get_longest_path(MY_HASH);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let paths = find_all_paths(MY_HASH, threads);
+    println!("Part 1: the shortest path is {:?}", paths.shortest);
     println!(
         "Part 2: the longest path has {} steps",
-        path2.unwrap().len()
+        paths.longest.unwrap().len()
     );
+    println!("There are {} ways through the vault", paths.count);
 }
 
 #[cfg(test)]
@@ -203,6 +281,19 @@ mod tests {
         assert_eq!(path.unwrap().len(), 830);
     }
 
+    #[test]
+    fn test_find_all_paths() {
+        let paths = find_all_paths("hijkl", 4);
+        assert_eq!(paths, VaultPaths::default());
+        let one_thread = find_all_paths("ihgpwlah", 1);
+        assert_eq!(one_thread.shortest, Some("DDRRRD".to_string()));
+        assert_eq!(one_thread.longest.as_ref().map(String::len), Some(370));
+        assert!(one_thread.count > 1);
+        for threads in [2, 3, 16] {
+            assert_eq!(find_all_paths("ihgpwlah", threads), one_thread);
+        }
+    }
+
     #[test]
     fn test_part_2() {
         let path = get_longest_path(MY_HASH);
@@ -1,4 +1,5 @@
-use std::collections::VecDeque;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use itertools::Itertools;
 use lazy_static::lazy_static;
@@ -13,6 +14,8 @@ lazy_static! {
 
 pub type Position = (u32, u32);
 pub type DataChunk = (Position, u32);
+// Moving all the data on one node to another.
+pub type Transfer = (Position, Position);
 
 #[derive(Clone, Eq, PartialEq)]
 pub struct Node {
@@ -88,19 +91,87 @@ impl Grid {
         for line in lines {
             nodes.push(Node::from_line(line));
         }
-        nodes.sort_by(|a, b| a.position.cmp(&b.position));
+        nodes.sort_by_key(|n| n.position);
         Grid { nodes }
     }
 
+    // The nodes are sorted by position, so they can be found by binary search.
+    fn index_of(&self, position: Position) -> Option<usize> {
+        self.nodes
+            .binary_search_by_key(&position, |n| n.position)
+            .ok()
+    }
+
+    fn node_at(&self, position: Position) -> Option<&Node> {
+        self.index_of(position).map(|idx| &self.nodes[idx])
+    }
+
+    fn neighbors(&self, position: Position) -> Vec<Position> {
+        let (x, y) = position;
+        let candidates = [
+            x.checked_sub(1).map(|x| (x, y)),
+            Some((x + 1, y)),
+            y.checked_sub(1).map(|y| (x, y)),
+            Some((x, y + 1)),
+        ];
+        candidates
+            .into_iter()
+            .flatten()
+            .filter(|&p| self.node_at(p).is_some())
+            .collect()
+    }
+
+    // The only node with no data on it, if there's exactly one.
+    fn empty_node(&self) -> Option<&Node> {
+        let mut empty = self.nodes.iter().filter(|n| n.used_tb == 0);
+        match (empty.next(), empty.next()) {
+            (Some(node), None) => Some(node),
+            _ => None,
+        }
+    }
+
+    // Nodes with more data than the empty node can hold, so their data can never move.
+    fn walls(&self, empty: &Node) -> HashSet<Position> {
+        self.nodes
+            .iter()
+            .filter(|n| n.used_tb > empty.size_tb)
+            .map(|n| n.position)
+            .collect()
+    }
+
+    // Where the data we want starts: the top right node.
+    fn goal_origin(&self) -> Position {
+        self.nodes
+            .iter()
+            .max_by_key(|n| n.position.0 as i32 - n.position.1 as i32)
+            .unwrap()
+            .position
+    }
+
+    // Where the data we want is now.
+    fn goal_position(&self) -> Position {
+        let origin = self.goal_origin();
+        self.nodes
+            .iter()
+            .find(|n| n.chunks.iter().any(|c| c.0 == origin))
+            .unwrap()
+            .position
+    }
+
     fn print(&self) {
+        let walls = match self.empty_node() {
+            Some(empty) => self.walls(empty),
+            None => HashSet::new(),
+        };
+        let goal = self.goal_position();
         for node in self.nodes.iter() {
             let (_, y) = node.position;
             if y == 0 {
                 println!();
             }
-            if node.used_tb > 100 {
+            if walls.contains(&node.position) {
                 print!("X");
-            } else if node.position == (37, 0) {
+            } else if node.position == goal {
                 print!("G");
             } else if node.used_tb > 0 {
                 print!(".");
@@ -125,63 +196,102 @@ impl Grid {
         })
     }
 
-    fn clone_and_move_data(&self, from: Position, to: Position) -> Grid {
-        let from_idx = self.nodes.iter().position(|n| n.position == from).unwrap();
-        let to_idx = self.nodes.iter().position(|n| n.position == to).unwrap();
-        let mut new_grid = self.clone();
-        let chunks = {
-            let source_node = new_grid.nodes.get_mut(from_idx).unwrap();
-            source_node.transfer_chunks()
+    fn move_data(&mut self, from: Position, to: Position) {
+        let (from_idx, to_idx) = match (self.index_of(from), self.index_of(to)) {
+            (Some(from_idx), Some(to_idx)) => (from_idx, to_idx),
+            _ => return,
         };
-        {
-            let dest_node = new_grid.nodes.get_mut(to_idx).unwrap();
-            dest_node.receive_chunks(chunks);
-        }
-        new_grid
+        let chunks = self.nodes[from_idx].transfer_chunks();
+        self.nodes[to_idx].receive_chunks(chunks);
     }
 
-    fn get_possible_states(&self) -> Vec<Grid> {
-        let mut valid_transfers = Vec::new();
-        for (a, b) in self.nodes.iter().tuple_combinations::<(_, _)>() {
-            if a.can_pair_with(b) && a.is_adjacent_to(b) {
-                valid_transfers.push((a, b));
+    // Replays `transfers` on a copy of the grid, or returns None if one isn't possible.
+    fn apply_transfers(&self, transfers: &[Transfer]) -> Option<Grid> {
+        let mut grid = self.clone();
+        for &(from, to) in transfers {
+            let (source, dest) = (grid.node_at(from)?, grid.node_at(to)?);
+            if !source.can_pair_with(dest) || !source.is_adjacent_to(dest) {
+                return None;
             }
-            if b.can_pair_with(a) && b.is_adjacent_to(a) {
-                valid_transfers.push((b, a));
-            }
-        }
-        let mut states = Vec::new();
-        for &(a, b) in valid_transfers.iter() {
-            states.push(self.clone_and_move_data(a.position, b.position));
+            grid.move_data(from, to);
         }
-        states
-    }
-
-    fn has_target_data_at_origin(&self, chunk: DataChunk) -> bool {
-        let origin = self.nodes.iter().find(|n| n.position == (0, 0)).unwrap();
-        origin.chunks.contains(&chunk)
+        Some(grid)
     }
 
-    fn optimize_data_movement(&self) -> Option<u32> {
-        let target_data = self
+    // Finds the fewest transfers that get the goal data to the origin. That's only simple
+    // when nothing fits on any node but the empty one, so all that ever happens is data
+    // moving into the empty node: the empty node and the goal data are all the state there
+    // is to search. This returns None for grids where that doesn't hold, or where the data
+    // can't get there:
+    // - some data fits on a node that isn't empty;
+    // - some data that can move doesn't fit on every node the empty node might reach;
+    // - some wall's data would fit on a node the empty node might reach, so it isn't
+    //   really a wall.
+    fn optimize_data_movement(&self) -> Option<Vec<Transfer>> {
+        let empty = self.empty_node()?;
+        let fits_elsewhere = self.nodes.iter().any(|source| {
+            self.nodes
+                .iter()
+                .any(|dest| dest.position != empty.position && source.can_pair_with(dest))
+        });
+        if fits_elsewhere {
+            return None;
+        }
+        let walls = self.walls(empty);
+        let (max_used, min_size, max_size) = self
             .nodes
             .iter()
-            .max_by_key(|n| n.position.0 as i32 - n.position.1 as i32)
-            .unwrap()
-            .chunks[0];
-        let mut q: VecDeque<(Grid, u32)> = VecDeque::new();
-        q.push_back((self.clone(), 0));
-        while !q.is_empty() {
-            print!("\rQueue: {:<}", q.len());
-            let (grid, n_steps) = q.pop_front().unwrap();
-            if grid.has_target_data_at_origin(target_data) {
-                return Some(n_steps);
+            .filter(|n| !walls.contains(&n.position))
+            .fold((0, u32::MAX, 0), |(used, min_size, max_size), n| {
+                (
+                    used.max(n.used_tb),
+                    min_size.min(n.size_tb),
+                    max_size.max(n.size_tb),
+                )
+            });
+        let min_wall = self
+            .nodes
+            .iter()
+            .filter(|n| walls.contains(&n.position))
+            .map(|n| n.used_tb)
+            .min()
+            .unwrap_or(u32::MAX);
+        if max_used > min_size || min_wall <= max_size {
+            return None;
+        }
+        let start = (empty.position, self.goal_position());
+        let mut came_from = HashMap::new();
+        came_from.insert(start, start);
+        let mut q = VecDeque::from(vec![start]);
+        let mut finish = None;
+        while let Some(state) = q.pop_front() {
+            let (empty, goal) = state;
+            if goal == (0, 0) {
+                finish = Some(state);
+                break;
             }
-            for state in grid.get_possible_states() {
-                q.push_back((state, n_steps + 1));
+            for next_empty in self.neighbors(empty) {
+                if walls.contains(&next_empty) {
+                    continue;
+                }
+                let next_goal = if next_empty == goal { empty } else { goal };
+                let next = (next_empty, next_goal);
+                if let Entry::Vacant(entry) = came_from.entry(next) {
+                    entry.insert(state);
+                    q.push_back(next);
+                }
             }
         }
-        None
+        let mut state = finish?;
+        let mut transfers = Vec::new();
+        while state != start {
+            let previous = came_from[&state];
+            // the empty node takes the place of the data that moved into it
+            transfers.push((state.0, previous.0));
+            state = previous;
+        }
+        transfers.reverse();
+        Some(transfers)
     }
 }
 
@@ -193,8 +303,12 @@ fn main() {
         "Part 1: There are {} viable pairs.",
         grid.count_viable_pairs()
     );
-    if let Some(n_steps) = grid.optimize_data_movement() {
-        println!("\nPart 2: It takes {} steps to move the data.", n_steps);
+    if let Some(transfers) = grid.optimize_data_movement() {
+        grid.apply_transfers(&transfers).unwrap().print();
+        println!(
+            "\nPart 2: It takes {} steps to move the data.",
+            transfers.len()
+        );
     } else {
         println!("\nPart 2: Could not move the data.");
     }
@@ -206,9 +320,9 @@ mod tests {
 
     fn create_node(position: Position, size_tb: u32) -> Node {
         Node {
-            position: position,
+            position,
             chunks: vec![],
-            size_tb: size_tb,
+            size_tb,
             used_tb: 0,
             available_tb: size_tb,
             // used_pct: 0,
@@ -240,17 +354,95 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // Long time
     fn test_example() {
-        let lines = InputReader::new("input.txt").parsed_lines();
+        let lines = InputReader::new("example.txt").parsed_lines();
         let grid = Grid::from_lines(lines);
-        let n_steps = grid.optimize_data_movement();
-        assert_eq!(n_steps, Some(7));
+        assert_eq!(
+            grid.walls(grid.empty_node().unwrap()),
+            HashSet::from([(0, 2)])
+        );
+        let transfers = grid.optimize_data_movement().unwrap();
+        assert_eq!(transfers.len(), 7);
+        assert_eq!(&transfers[..2], &[((1, 0), (1, 1)), ((2, 0), (1, 0))]);
+        let moved = grid.apply_transfers(&transfers).unwrap();
+        assert_eq!(moved.goal_position(), (0, 0));
+        // the transfers have to be done in order
+        assert!(grid.apply_transfers(&transfers[1..]).is_none());
+    }
+
+    #[test]
+    fn test_move_data() {
+        let lines = InputReader::new("example.txt").parsed_lines();
+        let mut grid = Grid::from_lines(lines);
+        assert_eq!(grid.index_of((0, 0)), Some(0));
+        assert_eq!(
+            grid.index_of((1, 1)).map(|idx| grid.nodes[idx].position),
+            Some((1, 1))
+        );
+        assert_eq!(grid.index_of((3, 0)), None);
+        let (from_used, to_used) = (
+            grid.node_at((1, 0)).unwrap().used_tb,
+            grid.node_at((1, 1)).unwrap().used_tb,
+        );
+        grid.move_data((1, 0), (1, 1));
+        assert_eq!(grid.node_at((1, 0)).unwrap().used_tb, 0);
+        assert_eq!(grid.node_at((1, 1)).unwrap().used_tb, from_used + to_used);
+        // moving to or from somewhere off the grid does nothing
+        let before = grid.nodes.clone();
+        grid.move_data((1, 1), (3, 0));
+        grid.move_data((3, 0), (1, 0));
+        assert!(grid.nodes == before);
+    }
+
+    #[test]
+    fn test_data_that_does_not_fit() {
+        let lines = InputReader::new("example.txt").parsed_lines();
+        let mut grid = Grid::from_lines(lines);
+        // a node too small to take its neighbours' data breaks the model
+        grid.nodes[0].size_tb = 7;
+        assert_eq!(grid.optimize_data_movement(), None);
+    }
+
+    // Gives the node at `position` a new size, holding `used` of its own data.
+    fn reshape(grid: &mut Grid, position: Position, size_tb: u32, used: u32) {
+        let idx = grid.index_of(position).unwrap();
+        let node = &mut grid.nodes[idx];
+        node.size_tb = size_tb;
+        node.chunks = vec![(position, used)];
+        node.update_storage_stats();
+    }
+
+    #[test]
+    fn test_data_that_fits_on_a_full_node() {
+        let lines = InputReader::new("example.txt").parsed_lines();
+        let mut grid = Grid::from_lines(lines);
+        // the top left node has room for its neighbours' data without being emptied
+        reshape(&mut grid, (0, 0), 20, 8);
+        assert_eq!(grid.optimize_data_movement(), None);
+    }
+
+    #[test]
+    fn test_wall_that_can_move() {
+        let lines = InputReader::new("example.txt").parsed_lines();
+        let mut grid = Grid::from_lines(lines);
+        // too much data for the empty node, but it fits on the bottom right node once the
+        // empty node gets there
+        reshape(&mut grid, (0, 2), 13, 12);
+        reshape(&mut grid, (2, 2), 13, 8);
+        assert_eq!(
+            grid.walls(grid.empty_node().unwrap()),
+            HashSet::from([(0, 2)])
+        );
+        assert_eq!(grid.optimize_data_movement(), None);
     }
 
     #[test]
     fn test_part_2() {
-        // takes 256 steps (17 + 22 + 37 + 5 * 36)
-        // done visually with grid.print()
+        let lines = InputReader::new("input.txt").parsed_lines();
+        let grid = Grid::from_lines(lines);
+        let transfers = grid.optimize_data_movement().unwrap();
+        assert_eq!(transfers.len(), 256);
+        let moved = grid.apply_transfers(&transfers).unwrap();
+        assert_eq!(moved.goal_position(), (0, 0));
     }
 }
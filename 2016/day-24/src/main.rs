@@ -1,10 +1,8 @@
 use std::collections::{HashSet, VecDeque};
-use std::hash::{Hash, Hasher};
+use std::fmt;
 
 use common::InputReader;
 
-const WATCH_QUEUE_SIZE: bool = false;
-
 type Coordinate = (usize, usize);
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -20,7 +18,7 @@ impl Item {
             '#' => Item::Wall,
             '.' => Item::Open,
             c => {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     Item::Waypoint(c.to_digit(10).unwrap() as usize)
                 } else {
                     panic!("Unexpected maze Item char: {}", c)
@@ -42,49 +40,27 @@ pub struct Maze {
     max_y: usize,
 }
 
-#[derive(Clone, Eq)]
-struct RobotState {
-    coords: Coordinate,
-    visited: Vec<bool>,
-    n_steps: u32,
-}
-
-impl PartialEq for RobotState {
-    fn eq(&self, other: &Self) -> bool {
-        self.coords == other.coords && self.visited == other.visited
-    }
+// A way for the robot to visit every waypoint, starting at 0.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Route {
+    // The waypoints in the order they're visited.
+    order: Vec<usize>,
+    // The steps from each waypoint in `order` to the next.
+    legs: Vec<u32>,
 }
 
-impl Hash for RobotState {
-    fn hash<H: Hasher>(&self, hasher: &mut H) {
-        self.coords.hash(hasher);
-        self.visited.hash(hasher);
+impl Route {
+    pub fn n_steps(&self) -> u32 {
+        self.legs.iter().sum()
     }
 }
 
-impl RobotState {
-    fn new_at_start(coords: Coordinate, n_waypoints: usize) -> RobotState {
-        let mut visited = vec![false; n_waypoints];
-        visited[0] = true;
-        RobotState {
-            coords,
-            visited,
-            n_steps: 0,
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (pair, leg) in self.order.windows(2).zip(self.legs.iter()) {
+            writeln!(f, "{} -> {}: {} steps", pair[0], pair[1], leg)?;
         }
-    }
-
-    fn has_visited_all(&self) -> bool {
-        self.visited.iter().all(|n| *n)
-    }
-
-    fn clone_and_move(&self, coords: Coordinate, item: Item) -> RobotState {
-        let mut new_state = self.clone();
-        new_state.coords = coords;
-        if let Item::Waypoint(n) = item {
-            new_state.visited[n] = true;
-        }
-        new_state.n_steps += 1;
-        new_state
+        writeln!(f, "Total: {} steps", self.n_steps())
     }
 }
 
@@ -154,62 +130,107 @@ impl Maze {
         steps
     }
 
-    fn minimize_steps(&self, return_to_start: bool) -> Option<u32> {
+    // The fewest steps from `start` to each waypoint, or None for those it can't reach.
+    fn distances_from(&self, start: Coordinate) -> Vec<Option<u32>> {
+        let mut distances = vec![None; self.n_waypoints];
         let mut q = VecDeque::new();
         let mut seen = HashSet::new();
-        let start_coords = self.get_waypoint_coords(0);
-        let start_state = RobotState::new_at_start(start_coords, self.n_waypoints);
-        seen.insert(start_state.clone());
-        q.push_back(start_state);
-        while !q.is_empty() {
-            if WATCH_QUEUE_SIZE && q.len() % 1000 < 5 {
-                print!("\rQueue: {}", q.len());
+        seen.insert(start);
+        q.push_back((start, 0));
+        while let Some((coords, n_steps)) = q.pop_front() {
+            if let Item::Waypoint(n) = self.get_item_at(coords) {
+                distances[n] = Some(n_steps);
             }
-            let state = q.pop_front().unwrap();
-            if state.has_visited_all() {
-                if return_to_start && state.coords == start_coords {
-                    if WATCH_QUEUE_SIZE {
-                        println!();
-                    }
-                    return Some(state.n_steps);
+            for new_coords in self.get_valid_steps_from(coords) {
+                if seen.insert(new_coords) {
+                    q.push_back((new_coords, n_steps + 1));
                 }
-                if !return_to_start {
-                    if WATCH_QUEUE_SIZE {
-                        println!();
+            }
+        }
+        distances
+    }
+
+    // Finds the shortest route through every waypoint (and back to 0, if asked) with the
+    // Held-Karp algorithm, working from the distances between each pair of waypoints.
+    fn plan_route(&self, return_to_start: bool) -> Option<Route> {
+        let n = self.n_waypoints;
+        let distances: Vec<Vec<Option<u32>>> = (0..n)
+            .map(|num| self.distances_from(self.get_waypoint_coords(num)))
+            .collect();
+        let all = (1 << n) - 1;
+        // best[visited][last] is the fewest steps to start at 0, visit the waypoints in the
+        // `visited` bitmask and finish at `last`, along with the waypoint visited before it.
+        let mut best: Vec<Vec<Option<(u32, usize)>>> = vec![vec![None; n]; 1 << n];
+        best[1][0] = Some((0, 0));
+        for visited in (1..=all).filter(|visited| visited & 1 == 1) {
+            for last in 0..n {
+                let Some((n_steps, _)) = best[visited][last] else {
+                    continue;
+                };
+                for next in (0..n).filter(|next| visited & (1 << next) == 0) {
+                    let Some(leg) = distances[last][next] else {
+                        continue;
+                    };
+                    let entry = &mut best[visited | (1 << next)][next];
+                    if entry.is_none_or(|(best_steps, _)| n_steps + leg < best_steps) {
+                        *entry = Some((n_steps + leg, last));
                     }
-                    return Some(state.n_steps);
                 }
             }
-            for coords in self.get_valid_steps_from(state.coords) {
-                let item = self.get_item_at(coords);
-                let new_state = state.clone_and_move(coords, item);
-                if !seen.contains(&new_state) {
-                    seen.insert(new_state.clone());
-                    q.push_back(new_state);
-                }
+        }
+        let finish = |last: usize| {
+            let (n_steps, _) = best[all][last]?;
+            if return_to_start {
+                Some(n_steps + distances[last][0]?)
+            } else {
+                Some(n_steps)
             }
+        };
+        let (_, last) = (0..n)
+            .filter_map(|last| finish(last).map(|n_steps| (n_steps, last)))
+            .min()?;
+        let mut order = vec![last];
+        let mut visited = all;
+        while order.len() < n {
+            let current = *order.last().unwrap();
+            let (_, previous) = best[visited][current].unwrap();
+            visited &= !(1 << current);
+            order.push(previous);
         }
-        None
+        order.reverse();
+        if return_to_start {
+            order.push(0);
+        }
+        let legs = order
+            .windows(2)
+            .map(|pair| distances[pair[0]][pair[1]].unwrap())
+            .collect();
+        Some(Route { order, legs })
     }
 }
 
 fn main() {
     let lines = InputReader::new("input.txt").parsed_lines();
     let maze = Maze::from_lines(lines);
-    if let Some(n_steps) = maze.minimize_steps(false) {
-        println!("\nPart 1: takes {} steps to visit all waypoints", n_steps);
+    if let Some(route) = maze.plan_route(false) {
+        print!("{}", route);
+        println!(
+            "Part 1: takes {} steps to visit all waypoints\n",
+            route.n_steps()
+        );
     } else {
-        println!("\nPart 1: could not find a path that covers all waypoints");
+        println!("Part 1: could not find a path that covers all waypoints\n");
     }
-    if let Some(n_steps) = maze.minimize_steps(true) {
+    if let Some(route) = maze.plan_route(true) {
+        print!("{}", route);
         println!(
-            "\nPart 2: takes {} steps to visit all waypoints and return \
+            "Part 2: takes {} steps to visit all waypoints and return \
                  to start",
-            n_steps
+            route.n_steps()
         );
     } else {
         println!(
-            "\nPart 2: could not find a path that covers all waypoints \
+            "Part 2: could not find a path that covers all waypoints \
                  and return to start"
         );
     }
@@ -223,15 +244,39 @@ mod tests {
     fn test_example() {
         let lines = InputReader::new("example.txt").parsed_lines();
         let maze = Maze::from_lines(lines);
-        let n_steps = maze.minimize_steps(false);
+        let n_steps = maze.plan_route(false).map(|route| route.n_steps());
         assert_eq!(n_steps, Some(14));
     }
 
+    #[test]
+    fn test_example_route() {
+        let lines = InputReader::new("example.txt").parsed_lines();
+        let maze = Maze::from_lines(lines);
+        let route = maze.plan_route(false).unwrap();
+        assert_eq!(route.order, vec![0, 4, 1, 2, 3]);
+        assert_eq!(route.legs, vec![2, 4, 6, 2]);
+        assert_eq!(
+            route.to_string(),
+            "0 -> 4: 2 steps\n4 -> 1: 4 steps\n1 -> 2: 6 steps\n2 -> 3: 2 steps\nTotal: 14 steps\n"
+        );
+        let route = maze.plan_route(true).unwrap();
+        assert_eq!(route.order.first(), Some(&0));
+        assert_eq!(route.order.last(), Some(&0));
+        assert_eq!(route.n_steps(), 20);
+    }
+
+    #[test]
+    fn test_unreachable_waypoint() {
+        let lines = vec!["#####", "#0.##", "###1#", "#####"];
+        let maze = Maze::from_lines(lines.into_iter().map(String::from).collect());
+        assert_eq!(maze.plan_route(false), None);
+    }
+
     #[test]
     fn test_part_1() {
         let lines = InputReader::new("input.txt").parsed_lines();
         let maze = Maze::from_lines(lines);
-        let n_steps = maze.minimize_steps(false);
+        let n_steps = maze.plan_route(false).map(|route| route.n_steps());
         assert_eq!(n_steps, Some(498));
     }
 
@@ -239,7 +284,7 @@ mod tests {
     fn test_part_2() {
         let lines = InputReader::new("input.txt").parsed_lines();
         let maze = Maze::from_lines(lines);
-        let n_steps = maze.minimize_steps(true);
+        let n_steps = maze.plan_route(true).map(|route| route.n_steps());
         assert_eq!(n_steps, Some(804));
     }
 }
@@ -0,0 +1,140 @@
+// Times the 2019 day 9 BOOST program, which exercises every opcode, relative mode and
+// far-away memory, against a copy of the interpreter loop from before the rewrite that
+// allocated while decoding every instruction. Run with `cargo bench -p intcode`.
+use std::time::{Duration, Instant};
+
+use common::InputReader;
+use intcode::{Computer, Val};
+
+const ROUNDS: u32 = 20;
+
+// The old interpreter, trimmed down to what BOOST needs: it decodes each instruction's
+// parameter modes, addresses and values into fresh vectors, and grows memory by resizing
+// the program.
+mod baseline {
+    use intcode::Val;
+
+    fn num_params(instruction: Val) -> usize {
+        match instruction {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => 0,
+            _ => panic!("Unknown Instruction {}", instruction),
+        }
+    }
+
+    fn get_modes(code: Val, num: usize) -> Vec<Val> {
+        let mut remainder = code / 100;
+        (0..num)
+            .map(|_| {
+                let mode = remainder % 10;
+                remainder /= 10;
+                mode
+            })
+            .collect()
+    }
+
+    // Runs `program` with a single input, returning its outputs.
+    pub fn run(mut program: Vec<Val>, input: Val) -> Vec<Val> {
+        let (mut pos, mut rb) = (0, 0);
+        let mut outputs = vec![];
+        loop {
+            let instruction = program[pos] % 100;
+            let n = num_params(instruction);
+            let modes = get_modes(program[pos], n);
+            let mut idxs = vec![];
+            let mut params = vec![];
+            for (i, mode) in modes.iter().enumerate() {
+                let param_val = program[pos + 1 + i];
+                let idx = match mode {
+                    0 => Some(param_val as usize),
+                    1 => None,
+                    2 => Some((param_val + rb) as usize),
+                    _ => panic!("Unknown ParamMode {}", mode),
+                };
+                let value = match idx {
+                    Some(idx) => program.get(idx).copied().unwrap_or(0),
+                    None => param_val,
+                };
+                idxs.push(idx);
+                params.push(value);
+            }
+            let k = match (n, instruction) {
+                (_, 3) => idxs[0].unwrap(),
+                (3, _) => idxs[2].unwrap(),
+                _ => 0,
+            };
+            if k >= program.len() {
+                program.resize(k + 1, 0);
+            }
+            let mut new_pos = pos + 1 + n;
+            match instruction {
+                1 => program[k] = params[0] + params[1],
+                2 => program[k] = params[0] * params[1],
+                3 => program[k] = input,
+                4 => outputs.push(params[0]),
+                5 if params[0] != 0 => new_pos = params[1] as usize,
+                6 if params[0] == 0 => new_pos = params[1] as usize,
+                7 => program[k] = (params[0] < params[1]) as Val,
+                8 => program[k] = (params[0] == params[1]) as Val,
+                9 => rb += params[0],
+                99 => return outputs,
+                _ => {}
+            }
+            pos = new_pos;
+        }
+    }
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn time_boost(comp: &Computer, input: Val) -> Duration {
+    time(|| {
+        let mut comp = comp.clone();
+        comp.set_input(input);
+        comp.execute();
+    })
+}
+
+fn main() {
+    let reader = InputReader::new("../day-09/input.txt");
+    let program: Vec<Val> = reader.parsed_csv_line();
+    let comp = Computer::from_reader(&reader);
+
+    let mut check = comp.clone();
+    check.set_input(2);
+    check.execute();
+    assert_eq!(
+        check.all_outputs(),
+        &baseline::run(program.clone(), 2),
+        "baseline disagrees with the computer"
+    );
+
+    println!("BOOST test mode (input=1): {:?}", time_boost(&comp, 1));
+    let before = time(|| {
+        baseline::run(program.clone(), 2);
+    });
+    let after = time_boost(&comp, 2);
+    println!("BOOST sensor mode (input=2), old loop: {:?}", before);
+    println!("BOOST sensor mode (input=2): {:?}", after);
+    println!(
+        "  {:.1}x faster",
+        before.as_secs_f64() / after.as_secs_f64()
+    );
+
+    let mut translated = comp.clone();
+    translated.enable_translation();
+    let translated = time_boost(&translated, 2);
+    println!("BOOST sensor mode, translated: {:?}", translated);
+    println!(
+        "  {:.1}x faster",
+        before.as_secs_f64() / translated.as_secs_f64()
+    );
+}
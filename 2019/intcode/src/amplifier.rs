@@ -0,0 +1,149 @@
+use std::thread;
+
+use crate::{Computer, Network, Val};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AmplifierMode {
+    // The signal passes through each amplifier once.
+    Serial,
+    // The last amplifier feeds back into the first until they all halt.
+    Feedback,
+}
+
+// A row of amplifiers all running the same program, each configured by a phase setting.
+#[derive(Clone)]
+pub struct AmplifierChain {
+    program: Computer,
+    mode: AmplifierMode,
+}
+
+impl AmplifierChain {
+    pub fn new(mut program: Computer, mode: AmplifierMode) -> AmplifierChain {
+        // Every amplifier in every run shares the one translation.
+        program.enable_translation();
+        AmplifierChain { program, mode }
+    }
+
+    pub fn serial(program: Computer) -> AmplifierChain {
+        AmplifierChain::new(program, AmplifierMode::Serial)
+    }
+
+    pub fn feedback(program: Computer) -> AmplifierChain {
+        AmplifierChain::new(program, AmplifierMode::Feedback)
+    }
+
+    // Sends each amplifier its phase, then `signal` into the first, and returns the last
+    // signal out of the final amplifier.
+    pub fn run(&self, phases: &[Val], signal: Val) -> Val {
+        let nodes = vec![self.program.clone(); phases.len()];
+        let mut network = match self.mode {
+            AmplifierMode::Serial => Network::chain(nodes),
+            AmplifierMode::Feedback => Network::feedback_loop(nodes),
+        };
+        if network.is_empty() {
+            return signal;
+        }
+        for (i, &phase) in phases.iter().enumerate() {
+            network.send(i, phase);
+        }
+        network.send(0, signal);
+        network.run();
+        *network
+            .node(network.len() - 1)
+            .final_output()
+            .expect("the last amplifier produced no signal")
+    }
+
+    // Tries every ordering of `phases` with an input signal of 0, returning the ordering
+    // that gives the highest signal (the first one found, on ties) and that signal. The
+    // orderings are split across `threads` threads.
+    pub fn search(&self, phases: &[Val], threads: usize) -> (Vec<Val>, Val) {
+        let candidates = permutations(phases);
+        let chunk_size = candidates.len().div_ceil(threads.max(1)).max(1);
+        let signals: Vec<Val> = thread::scope(|scope| {
+            let workers: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().map(|p| self.run(p, 0)).collect::<Vec<_>>())
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        let best = (0..signals.len())
+            .rev()
+            .max_by_key(|&i| signals[i])
+            .expect("no phases to search");
+        (candidates[best].clone(), signals[best])
+    }
+}
+
+// Every ordering of `items`, in lexicographic order of positions.
+pub fn permutations<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    if items.len() <= 1 {
+        return vec![items.to_vec()];
+    }
+    let mut all = vec![];
+    for i in 0..items.len() {
+        let mut rest = items.to_vec();
+        let first = rest.remove(i);
+        for mut tail in permutations(&rest) {
+            tail.insert(0, first.clone());
+            all.push(tail);
+        }
+    }
+    all
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutations() {
+        assert_eq!(
+            permutations(&[0, 1, 2]),
+            vec![
+                vec![0, 1, 2],
+                vec![0, 2, 1],
+                vec![1, 0, 2],
+                vec![1, 2, 0],
+                vec![2, 0, 1],
+                vec![2, 1, 0],
+            ]
+        );
+        assert_eq!(permutations::<Val>(&[]), vec![vec![]]);
+        assert_eq!(permutations(&['a'; 4]).len(), 24);
+    }
+
+    #[test]
+    fn test_serial() {
+        let chain = AmplifierChain::serial(Computer::new(vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ]));
+        assert_eq!(chain.run(&[4, 3, 2, 1, 0], 0), 43210);
+        assert_eq!(chain.run(&[], 7), 7);
+        assert_eq!(
+            chain.search(&[0, 1, 2, 3, 4], 1),
+            (vec![4, 3, 2, 1, 0], 43210)
+        );
+    }
+
+    #[test]
+    fn test_feedback() {
+        let chain = AmplifierChain::feedback(Computer::new(vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ]));
+        assert_eq!(chain.run(&[9, 8, 7, 6, 5], 0), 139629729);
+        let phases: Vec<Val> = (5..10).collect();
+        for threads in [1, 3, 8, 200] {
+            assert_eq!(
+                chain.search(&phases, threads),
+                (vec![9, 8, 7, 6, 5], 139629729)
+            );
+        }
+    }
+}
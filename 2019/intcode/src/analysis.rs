@@ -0,0 +1,523 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::{Computer, Instruction, ParamMode, Val, MODE_DIVISORS};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operand {
+    Position(Val),
+    Immediate(Val),
+    Relative(Val),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Position(addr) => write!(f, "[{}]", addr),
+            Operand::Immediate(value) => write!(f, "{}", value),
+            Operand::Relative(offset) if *offset < 0 => write!(f, "[rb{}]", offset),
+            Operand::Relative(offset) => write!(f, "[rb+{}]", offset),
+        }
+    }
+}
+
+// A statically decoded instruction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Op {
+    pub addr: usize,
+    pub instruction: Instruction,
+    pub operands: Vec<Operand>,
+}
+
+impl Op {
+    // Decodes the instruction at `addr`, or `None` if it isn't a valid instruction.
+    pub fn decode(program: &[Val], addr: usize) -> Option<Op> {
+        let code = *program.get(addr)?;
+        let instruction = Instruction::decode(code % 100)?;
+        let params = program.get(addr + 1..=addr + instruction.num_params())?;
+        let mut operands = vec![];
+        for (i, &value) in params.iter().enumerate() {
+            let operand = match ParamMode::decode(code / MODE_DIVISORS[i + 1] % 10)? {
+                ParamMode::Position => Operand::Position(value),
+                ParamMode::Immediate => Operand::Immediate(value),
+                ParamMode::Relative => Operand::Relative(value),
+            };
+            operands.push(operand);
+        }
+        let op = Op {
+            addr,
+            instruction,
+            operands,
+        };
+        match op.write_target() {
+            Some(Operand::Immediate(_)) => None,
+            _ => Some(op),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    pub fn next(&self) -> usize {
+        self.addr + self.size()
+    }
+
+    pub fn write_target(&self) -> Option<Operand> {
+        self.instruction.write_param().map(|n| self.operands[n - 1])
+    }
+
+    // The value this instruction writes, if it only depends on immediate operands.
+    fn constant_result(&self) -> Option<Val> {
+        match (self.instruction, &self.operands[..]) {
+            (Instruction::Add, [Operand::Immediate(a), Operand::Immediate(b), _]) => Some(a + b),
+            (Instruction::Mul, [Operand::Immediate(a), Operand::Immediate(b), _]) => Some(a * b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.addr, self.instruction)?;
+        let write_param = self.instruction.write_param();
+        let reads: Vec<String> = self
+            .operands
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| Some(i + 1) != write_param)
+            .map(|(_, operand)| operand.to_string())
+            .collect();
+        if !reads.is_empty() {
+            write!(f, " {}", reads.join(", "))?;
+        }
+        if let Some(target) = self.write_target() {
+            write!(f, " -> {}", target)?;
+        }
+        Ok(())
+    }
+}
+
+// Decodes every instruction reachable from address 0 by following known control flow.
+pub fn disassemble(program: &[Val]) -> Vec<Op> {
+    ControlFlowGraph::build(program)
+        .blocks
+        .into_values()
+        .flat_map(|block| block.ops)
+        .collect()
+}
+
+// How control leaves a basic block.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Exit {
+    // Runs straight into the block starting at this address.
+    Next(usize),
+    Halt,
+    Jump(usize),
+    Branch { taken: usize, not_taken: usize },
+    // A jump made after storing the return address on the relative-base stack.
+    Call { target: usize, ret: usize },
+    // An unconditional jump to an address read from the relative-base stack.
+    Return,
+    // A jump to a computed address, which may not be taken if it's conditional.
+    Indirect { not_taken: Option<usize> },
+    // Runs into something that doesn't decode as an instruction.
+    Invalid(usize),
+}
+
+impl Exit {
+    pub fn edges(&self) -> Vec<Edge> {
+        let edge = |to, kind| Edge { to, kind };
+        match *self {
+            Exit::Next(to) => vec![edge(to, EdgeKind::Fallthrough)],
+            Exit::Jump(to) => vec![edge(to, EdgeKind::Jump)],
+            Exit::Branch { taken, not_taken } => vec![
+                edge(taken, EdgeKind::Jump),
+                edge(not_taken, EdgeKind::Fallthrough),
+            ],
+            Exit::Call { target, ret } => vec![
+                edge(target, EdgeKind::Call),
+                edge(ret, EdgeKind::Fallthrough),
+            ],
+            Exit::Indirect {
+                not_taken: Some(to),
+            } => vec![edge(to, EdgeKind::Fallthrough)],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EdgeKind {
+    Fallthrough,
+    Jump,
+    Call,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Edge {
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub ops: Vec<Op>,
+    pub exit: Exit,
+}
+
+impl Block {
+    pub fn end(&self) -> usize {
+        self.ops.last().map_or(self.start, |op| op.next())
+    }
+
+    pub fn edges(&self) -> Vec<Edge> {
+        self.exit.edges()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Function {
+    pub entry: usize,
+    pub call_sites: Vec<usize>,
+}
+
+// A write into memory that holds a decoded instruction. Only writes to fixed (position
+// mode) addresses can be detected statically.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SelfModification {
+    pub writer: usize,
+    pub target: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ControlFlowGraph {
+    pub blocks: BTreeMap<usize, Block>,
+    pub functions: Vec<Function>,
+    pub self_modifications: Vec<SelfModification>,
+}
+
+impl ControlFlowGraph {
+    pub fn build(program: &[Val]) -> ControlFlowGraph {
+        let ops = decode_reachable(program);
+        let ends_at: BTreeMap<usize, usize> = ops.values().map(|op| (op.next(), op.addr)).collect();
+        let exit_of = |op: &Op| {
+            let prev = ends_at.get(&op.addr).and_then(|addr| ops.get(addr));
+            terminator_exit(op, prev)
+        };
+
+        let mut leaders: BTreeSet<usize> = BTreeSet::new();
+        if ops.contains_key(&0) {
+            leaders.insert(0);
+        }
+        for op in ops.values() {
+            if let Some(exit) = exit_of(op) {
+                leaders.extend(exit.edges().iter().map(|e| e.to));
+            }
+        }
+        leaders.retain(|addr| ops.contains_key(addr));
+
+        let mut blocks = BTreeMap::new();
+        let mut functions: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for &start in &leaders {
+            let mut block_ops = vec![];
+            let mut addr = start;
+            let exit = loop {
+                let op = match ops.get(&addr) {
+                    Some(op) => op,
+                    None => break Exit::Invalid(addr),
+                };
+                block_ops.push(op.clone());
+                if let Some(exit) = exit_of(op) {
+                    if let Exit::Call { target, .. } = exit {
+                        functions.entry(target).or_default().push(op.addr);
+                    }
+                    break exit;
+                }
+                addr = op.next();
+                if leaders.contains(&addr) {
+                    break Exit::Next(addr);
+                }
+            };
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    ops: block_ops,
+                    exit,
+                },
+            );
+        }
+
+        let mut self_modifications = vec![];
+        for op in ops.values() {
+            if let Some(Operand::Position(target)) = op.write_target() {
+                let target = target as usize;
+                let hits_code = ops
+                    .range(..=target)
+                    .next_back()
+                    .is_some_and(|(_, code)| target < code.next());
+                if hits_code {
+                    self_modifications.push(SelfModification {
+                        writer: op.addr,
+                        target,
+                    });
+                }
+            }
+        }
+
+        ControlFlowGraph {
+            blocks,
+            functions: functions
+                .into_iter()
+                .map(|(entry, call_sites)| Function { entry, call_sites })
+                .collect(),
+            self_modifications,
+        }
+    }
+
+    pub fn block_containing(&self, addr: usize) -> Option<&Block> {
+        self.blocks
+            .range(..=addr)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| addr < block.end())
+    }
+
+    // Renders the graph for Graphviz. Function entries get a double border and blocks that
+    // are overwritten at runtime are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let entries: BTreeSet<usize> = self.functions.iter().map(|f| f.entry).collect();
+        let modified: BTreeSet<usize> = self
+            .self_modifications
+            .iter()
+            .filter_map(|m| self.block_containing(m.target))
+            .map(|block| block.start)
+            .collect();
+        let mut out = String::from("digraph intcode {\n");
+        out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        let mut missing = BTreeSet::new();
+        for block in self.blocks.values() {
+            let mut label: String = block.ops.iter().map(|op| format!("{}\\l", op)).collect();
+            match block.exit {
+                Exit::Return => label.push_str("(return)\\l"),
+                Exit::Indirect { .. } => label.push_str("(indirect)\\l"),
+                Exit::Invalid(addr) => label.push_str(&format!("(invalid at {})\\l", addr)),
+                _ => {}
+            }
+            let mut attrs = format!("label=\"{}\"", label);
+            if entries.contains(&block.start) {
+                attrs.push_str(", peripheries=2");
+            }
+            if modified.contains(&block.start) {
+                attrs.push_str(", color=red");
+            }
+            out.push_str(&format!("    b{} [{}];\n", block.start, attrs));
+            for edge in block.edges() {
+                if !self.blocks.contains_key(&edge.to) {
+                    missing.insert(edge.to);
+                }
+                let style = match edge.kind {
+                    EdgeKind::Fallthrough => " [style=dashed]",
+                    EdgeKind::Jump => "",
+                    EdgeKind::Call => " [style=bold, label=\"call\"]",
+                };
+                out.push_str(&format!("    b{} -> b{}{};\n", block.start, edge.to, style));
+            }
+        }
+        for addr in missing {
+            out.push_str(&format!(
+                "    b{} [label=\"{}: ???\", style=dotted];\n",
+                addr, addr
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl Computer {
+    pub fn control_flow_graph(&self) -> ControlFlowGraph {
+        ControlFlowGraph::build(self.memory.dense())
+    }
+}
+
+// For an instruction that ends a basic block, how it leaves. `prev` is the instruction
+// immediately before it, used to spot calls.
+fn terminator_exit(op: &Op, prev: Option<&Op>) -> Option<Exit> {
+    let jump_if = match op.instruction {
+        Instruction::End => return Some(Exit::Halt),
+        Instruction::JumpT => true,
+        Instruction::JumpF => false,
+        _ => return None,
+    };
+    let always = match op.operands[0] {
+        Operand::Immediate(cond) if (cond != 0) == jump_if => true,
+        // Never taken, so it's just a (strange) no-op.
+        Operand::Immediate(_) => return None,
+        _ => false,
+    };
+    let target = match op.operands[1] {
+        Operand::Immediate(target) => usize::try_from(target).ok(),
+        _ => None,
+    };
+    let exit = match (always, target) {
+        (true, Some(target)) => {
+            let pushes_return = prev.is_some_and(|prev| {
+                matches!(prev.write_target(), Some(Operand::Relative(_)))
+                    && prev.constant_result() == Some(op.next() as Val)
+            });
+            if pushes_return {
+                Exit::Call {
+                    target,
+                    ret: op.next(),
+                }
+            } else {
+                Exit::Jump(target)
+            }
+        }
+        (true, None) if matches!(op.operands[1], Operand::Relative(_)) => Exit::Return,
+        (true, None) => Exit::Indirect { not_taken: None },
+        (false, Some(target)) => Exit::Branch {
+            taken: target,
+            not_taken: op.next(),
+        },
+        (false, None) => Exit::Indirect {
+            not_taken: Some(op.next()),
+        },
+    };
+    Some(exit)
+}
+
+// Recursive descent from address 0. Call detection depends on the instruction before a jump,
+// which may be decoded after the jump itself, so this repeats until no new targets appear.
+fn decode_reachable(program: &[Val]) -> BTreeMap<usize, Op> {
+    let mut ops: BTreeMap<usize, Op> = BTreeMap::new();
+    let mut invalid = BTreeSet::new();
+    let mut work = vec![0];
+    while !work.is_empty() {
+        while let Some(addr) = work.pop() {
+            if ops.contains_key(&addr) || invalid.contains(&addr) {
+                continue;
+            }
+            let op = match Op::decode(program, addr) {
+                Some(op) => op,
+                None => {
+                    invalid.insert(addr);
+                    continue;
+                }
+            };
+            match terminator_exit(&op, None) {
+                None => work.push(op.next()),
+                Some(exit) => work.extend(exit.edges().iter().map(|e| e.to)),
+            }
+            ops.insert(addr, op);
+        }
+        let ends_at: BTreeMap<usize, usize> = ops.values().map(|op| (op.next(), op.addr)).collect();
+        for op in ops.values() {
+            let prev = ends_at.get(&op.addr).and_then(|addr| ops.get(addr));
+            if let Some(Exit::Call { ret, .. }) = terminator_exit(op, prev) {
+                if !ops.contains_key(&ret) && !invalid.contains(&ret) {
+                    work.push(ret);
+                }
+            }
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::InputReader;
+
+    #[test]
+    fn test_disassemble() {
+        let ops = disassemble(&[1002, 4, 3, 4, 33]);
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].to_string(), "0: Mul [4], 3 -> [4]");
+        let ops = disassemble(&[109, -1, 204, 1, 99]);
+        let text: Vec<String> = ops.iter().map(|op| op.to_string()).collect();
+        assert_eq!(text, vec!["0: RbOffset -1", "2: Output [rb+1]", "4: End"]);
+    }
+
+    #[test]
+    fn test_self_modification() {
+        let cfg = ControlFlowGraph::build(&[1, 1, 1, 4, 99, 5, 6, 0, 99]);
+        assert_eq!(
+            cfg.self_modifications,
+            vec![SelfModification {
+                writer: 0,
+                target: 4
+            }]
+        );
+        assert!(cfg
+            .to_dot()
+            .contains("b0 [label=\"0: Add [1], [1] -> [4]\\l4: End\\l\", color=red]"));
+    }
+
+    #[test]
+    fn test_branches() {
+        // 2019 day 5 example comparing the input to 8.
+        let cfg = ControlFlowGraph::build(&[
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ]);
+        assert_eq!(
+            cfg.blocks.keys().copied().collect::<Vec<_>>(),
+            vec![0, 9, 16, 22, 31, 36, 46]
+        );
+        assert_eq!(
+            cfg.blocks[&0].exit,
+            Exit::Branch {
+                taken: 22,
+                not_taken: 9
+            }
+        );
+        assert_eq!(cfg.blocks[&16].exit, Exit::Jump(36));
+        assert_eq!(cfg.blocks[&46].exit, Exit::Halt);
+        assert!(cfg.functions.is_empty());
+        assert!(cfg.self_modifications.is_empty());
+    }
+
+    #[test]
+    fn test_function_call() {
+        // Calls a function at 12 that prints 42 and returns.
+        let program = vec![
+            109, 100, 21101, 9, 0, 0, 1105, 1, 12, 99, 0, 0, 104, 42, 2106, 0, 0,
+        ];
+        let mut comp = Computer::new(program);
+        let cfg = comp.control_flow_graph();
+        assert_eq!(
+            cfg.functions,
+            vec![Function {
+                entry: 12,
+                call_sites: vec![6]
+            }]
+        );
+        assert_eq!(cfg.blocks[&0].exit, Exit::Call { target: 12, ret: 9 });
+        assert_eq!(cfg.blocks[&12].exit, Exit::Return);
+        assert_eq!(cfg.blocks[&9].exit, Exit::Halt);
+        assert!(cfg
+            .to_dot()
+            .contains("b0 -> b12 [style=bold, label=\"call\"];"));
+        comp.execute();
+        assert_eq!(comp.all_outputs(), &vec![42]);
+    }
+
+    #[test]
+    fn test_boost_program() {
+        let comp = Computer::from_reader(&InputReader::new("../day-09/input.txt"));
+        let cfg = comp.control_flow_graph();
+        // The recursive function used by sensor mode.
+        assert!(cfg.functions.contains(&Function {
+            entry: 922,
+            call_sites: vec![912, 939, 954]
+        }));
+        assert_eq!(cfg.block_containing(970).unwrap().exit, Exit::Return);
+        assert!(cfg.self_modifications.is_empty());
+        assert!(cfg.to_dot().starts_with("digraph intcode {\n"));
+    }
+}
@@ -0,0 +1,278 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{Computer, Grid, Position, RunState, Val};
+
+// Clears the terminal and moves the cursor to the top left.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+// Resets colours and attributes and shows the cursor again, leaving the terminal as we found it.
+const RESET_TERMINAL: &str = "\x1b[0m\x1b[?25h";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Tile {
+    Empty = 0,
+    Wall = 1,
+    Block = 2,
+    Paddle = 3,
+    Ball = 4,
+}
+
+impl From<Val> for Tile {
+    fn from(value: Val) -> Self {
+        match value {
+            0 => Tile::Empty,
+            1 => Tile::Wall,
+            2 => Tile::Block,
+            3 => Tile::Paddle,
+            4 => Tile::Ball,
+            _ => panic!("Unknown tile: {}", value),
+        }
+    }
+}
+
+impl Tile {
+    pub fn glyph(self) -> char {
+        match self {
+            Tile::Empty => ' ',
+            Tile::Wall => '#',
+            Tile::Block => '=',
+            Tile::Paddle => '-',
+            Tile::Ball => 'o',
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Joystick {
+    Left = -1,
+    Neutral = 0,
+    Right = 1,
+}
+
+// An arcade cabinet running an intcode game. The program draws by outputting `(x, y, tile)`
+// triples, except that `(-1, 0, score)` updates the score display, and reads the joystick
+// position whenever it wants input.
+pub struct Arcade {
+    computer: Computer,
+    screen: Grid,
+    score: Val,
+    ball: Option<Position>,
+    paddle: Option<Position>,
+}
+
+impl Arcade {
+    pub fn new(computer: Computer) -> Arcade {
+        Arcade {
+            computer,
+            screen: Grid::new(Tile::Empty as Val),
+            score: 0,
+            ball: None,
+            paddle: None,
+        }
+    }
+
+    // Sets the number of credits, which is how the game is switched into free play.
+    pub fn insert_quarters(&mut self, quarters: Val) {
+        self.computer.write(0, quarters);
+    }
+
+    pub fn screen(&self) -> &Grid {
+        &self.screen
+    }
+
+    pub fn score(&self) -> Val {
+        self.score
+    }
+
+    pub fn ball(&self) -> Option<Position> {
+        self.ball
+    }
+
+    pub fn paddle(&self) -> Option<Position> {
+        self.paddle
+    }
+
+    pub fn tile(&self, pos: Position) -> Tile {
+        Tile::from(self.screen.get(pos))
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.screen.count(Tile::Block as Val)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.computer.is_finished()
+    }
+
+    // Draws everything the game outputs until it wants input or halts.
+    pub fn update(&mut self) -> RunState {
+        loop {
+            let x = match self.computer.run_until_output() {
+                RunState::Output(x) => x,
+                state => return state,
+            };
+            let mut next = || match self.computer.run_until_output() {
+                RunState::Output(value) => value,
+                state => panic!("game stopped mid-triple: {:?}", state),
+            };
+            let (y, value) = (next(), next());
+            if (x, y) == (-1, 0) {
+                self.score = value;
+                continue;
+            }
+            match Tile::from(value) {
+                Tile::Ball => self.ball = Some((x, y)),
+                Tile::Paddle => self.paddle = Some((x, y)),
+                _ => {}
+            }
+            self.screen.set((x, y), value);
+        }
+    }
+
+    pub fn move_joystick(&mut self, joystick: Joystick) {
+        self.computer.set_input(joystick as Val);
+    }
+
+    // Keeps the paddle under the ball.
+    pub fn follow_ball(&self) -> Joystick {
+        match (self.ball, self.paddle) {
+            (Some((ball, _)), Some((paddle, _))) if ball < paddle => Joystick::Left,
+            (Some((ball, _)), Some((paddle, _))) if ball > paddle => Joystick::Right,
+            _ => Joystick::Neutral,
+        }
+    }
+
+    // Plays until the game halts, asking `strategy` for the joystick position every frame,
+    // and returns the final score.
+    pub fn play<F: FnMut(&Arcade) -> Joystick>(&mut self, mut strategy: F) -> Val {
+        while self.update() == RunState::NeedsInput {
+            let joystick = strategy(self);
+            self.move_joystick(joystick);
+        }
+        self.score
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = self.screen.render(|value| Tile::from(value).glyph());
+        out.push_str(&format!("Score: {}\n", self.score));
+        out
+    }
+
+    // Lets a person play, redrawing the screen every frame. This reads whole lines rather
+    // than raw key presses, so every move needs enter: `a` for left, `d` for right, anything
+    // else (e.g. just enter) leaves the joystick in the middle. Returns the score when the
+    // game ends or the input runs out, after resetting the terminal either way.
+    pub fn play_interactive<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        mut output: W,
+    ) -> io::Result<Val> {
+        let mut finished = true;
+        while self.update() == RunState::NeedsInput {
+            write!(output, "{}{}", CLEAR_SCREEN, self.render())?;
+            write!(output, "[a] left, [d] right, [enter] stay: ")?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                finished = false;
+                break;
+            }
+            self.move_joystick(match line.trim() {
+                "a" => Joystick::Left,
+                "d" => Joystick::Right,
+                _ => Joystick::Neutral,
+            });
+        }
+        if finished {
+            write!(output, "{}{}", CLEAR_SCREEN, self.render())?;
+            writeln!(output, "Game over")?;
+        } else {
+            writeln!(output)?;
+        }
+        write!(output, "{}", RESET_TERMINAL)?;
+        output.flush()?;
+        Ok(self.score)
+    }
+
+    pub fn play_in_terminal(&mut self) -> io::Result<Val> {
+        self.play_interactive(io::stdin().lock(), io::stdout())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws one frame, moves the paddle by the joystick position, scores 42 and halts.
+    fn tiny_game() -> Computer {
+        Computer::new(vec![
+            104, 0, 104, 0, 104, 1, // wall at (0, 0)
+            104, 2, 104, 0, 104, 4, // ball at (2, 0)
+            104, 1, 104, 1, 104, 3, // paddle at (1, 1)
+            104, 3, 104, 0, 104, 2, // block at (3, 0)
+            3, 100, 1001, 100, 1, 101, // [101] = joystick + 1
+            104, 1, 104, 1, 104, 0, // erase the paddle
+            4, 101, 104, 1, 104, 3, // redraw it at ([101], 1)
+            104, -1, 104, 0, 104, 42, // score
+            99,
+        ])
+    }
+
+    #[test]
+    fn test_update() {
+        let mut arcade = Arcade::new(tiny_game());
+        assert_eq!(arcade.update(), RunState::NeedsInput);
+        assert_eq!(arcade.ball(), Some((2, 0)));
+        assert_eq!(arcade.paddle(), Some((1, 1)));
+        assert_eq!(arcade.tile((0, 0)), Tile::Wall);
+        assert_eq!(arcade.blocks(), 1);
+        assert_eq!(arcade.render(), "# o=\n -  \nScore: 0\n");
+        assert_eq!(arcade.follow_ball(), Joystick::Right);
+    }
+
+    #[test]
+    fn test_autoplay() {
+        let mut arcade = Arcade::new(tiny_game());
+        assert_eq!(arcade.play(Arcade::follow_ball), 42);
+        assert!(arcade.is_finished());
+        assert_eq!(arcade.paddle(), Some((2, 1)));
+        assert_eq!(arcade.render(), "# o=\n  - \nScore: 42\n");
+    }
+
+    #[test]
+    fn test_play_interactive() {
+        let mut arcade = Arcade::new(tiny_game());
+        let mut screen = vec![];
+        assert_eq!(
+            arcade.play_interactive(&b"a\n"[..], &mut screen).unwrap(),
+            42
+        );
+        assert_eq!(arcade.paddle(), Some((0, 1)));
+        let screen = String::from_utf8(screen).unwrap();
+        assert_eq!(screen.matches(CLEAR_SCREEN).count(), 2);
+        assert!(screen.ends_with(&format!("-   \nScore: 42\nGame over\n{}", RESET_TERMINAL)));
+    }
+
+    #[test]
+    fn test_play_interactive_out_of_input() {
+        let mut arcade = Arcade::new(tiny_game());
+        let mut screen = vec![];
+        assert_eq!(arcade.play_interactive(&b""[..], &mut screen).unwrap(), 0);
+        assert!(!arcade.is_finished());
+        let screen = String::from_utf8(screen).unwrap();
+        assert_eq!(screen.matches(CLEAR_SCREEN).count(), 1);
+        assert!(screen.ends_with(&format!("stay: \n{}", RESET_TERMINAL)));
+    }
+
+    #[test]
+    fn test_insert_quarters() {
+        // Draws the tile [11] + [12] at (0, 0), unless the credits turn the add into a multiply.
+        let game = Computer::new(vec![1, 11, 12, 13, 104, 0, 104, 0, 4, 13, 99, 1, 2, 0]);
+        let mut arcade = Arcade::new(game.clone());
+        arcade.update();
+        assert_eq!(arcade.tile((0, 0)), Tile::Paddle);
+        let mut arcade = Arcade::new(game);
+        arcade.insert_quarters(2);
+        assert_eq!(arcade.update(), RunState::Halted);
+        assert_eq!(arcade.tile((0, 0)), Tile::Block);
+    }
+}
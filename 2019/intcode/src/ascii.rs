@@ -0,0 +1,115 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{Computer, RunState, Val};
+
+// Outputs split into printable text and any values outside the ASCII range (often the
+// puzzle answer).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AsciiOutput {
+    pub text: String,
+    pub values: Vec<Val>,
+}
+
+impl From<&[Val]> for AsciiOutput {
+    fn from(outputs: &[Val]) -> AsciiOutput {
+        let mut ascii = AsciiOutput::default();
+        for &value in outputs {
+            match u8::try_from(value) {
+                Ok(byte) if byte.is_ascii() => ascii.text.push(byte as char),
+                _ => ascii.values.push(value),
+            }
+        }
+        ascii
+    }
+}
+
+impl Computer {
+    // Queues each character of `line` followed by a newline.
+    pub fn set_ascii_input(&mut self, line: &str) {
+        for byte in line.bytes() {
+            self.set_input(byte as Val);
+        }
+        self.set_input(b'\n' as Val);
+    }
+
+    // Runs until more input is needed or the program ends, collecting what was printed.
+    pub fn run_ascii(&mut self) -> (RunState, AsciiOutput) {
+        let start = self.outputs.len();
+        let state = loop {
+            match self.run_until_output() {
+                RunState::Output(_) => continue,
+                state => break state,
+            }
+        };
+        (state, AsciiOutput::from(&self.outputs[start..]))
+    }
+
+    // Lets a human drive the program: prints its output, then sends each line typed on
+    // `input` as a command. Returns when the program halts or `input` is exhausted.
+    pub fn interact<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        mut output: W,
+    ) -> io::Result<RunState> {
+        loop {
+            let (state, printed) = self.run_ascii();
+            write!(output, "{}", printed.text)?;
+            for value in printed.values {
+                writeln!(output, "[{}]", value)?;
+            }
+            output.flush()?;
+            if state == RunState::Halted {
+                return Ok(state);
+            }
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(state);
+            }
+            self.set_ascii_input(line.trim_end_matches(&['\r', '\n'][..]));
+        }
+    }
+
+    pub fn interact_in_terminal(&mut self) -> io::Result<RunState> {
+        self.interact(io::stdin().lock(), io::stdout())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Prints "> ", then echoes one line of input back, forever.
+    fn echo() -> Computer {
+        let mut program = vec![
+            104, 62, 104, 32, 3, 100, 4, 100, 1008, 100, 10, 101, 1006, 101, 4, 1105, 1, 0,
+        ];
+        program.resize(102, 0);
+        Computer::new(program)
+    }
+
+    #[test]
+    fn test_ascii_output() {
+        let output = AsciiOutput::from(&[72, 105, 10, 1234, -1][..]);
+        assert_eq!(output.text, "Hi\n");
+        assert_eq!(output.values, vec![1234, -1]);
+    }
+
+    #[test]
+    fn test_run_ascii() {
+        let mut comp = echo();
+        assert_eq!(comp.run_ascii().1.text, "> ");
+        comp.set_ascii_input("hello");
+        let (state, printed) = comp.run_ascii();
+        assert_eq!(state, RunState::NeedsInput);
+        assert_eq!(printed.text, "hello\n> ");
+    }
+
+    #[test]
+    fn test_interact() {
+        let mut comp = echo();
+        let mut screen = vec![];
+        let state = comp.interact(&b"north\nsouth\n"[..], &mut screen).unwrap();
+        assert_eq!(state, RunState::NeedsInput);
+        assert_eq!(String::from_utf8(screen).unwrap(), "> north\n> south\n> ");
+    }
+}
@@ -0,0 +1,399 @@
+// Differential testing: random programs run on `Computer` and on a deliberately simple
+// reference interpreter must agree step for step. Failures are shrunk to a minimal program.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{Computer, RunState, Val};
+
+const MAX_STEPS: usize = 2000;
+const DATA_CELLS: usize = 16;
+
+// xorshift64*, so the tests need no dependencies and are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn range(&mut self, lo: Val, hi: Val) -> Val {
+        lo + self.below((hi - lo + 1) as usize) as Val
+    }
+}
+
+// Generates a program of arithmetic, comparisons, I/O, jumps and relative base moves,
+// followed by some data cells. Jumps mostly land on instruction boundaries, and most
+// writes go to the data cells, but nothing stops a program from misbehaving; the
+// reference interpreter just stops comparing once a program faults.
+fn random_program(rng: &mut Rng) -> Vec<Val> {
+    let opcodes = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+    let num_params = |op: Val| match op {
+        1 | 2 | 7 | 8 => 3,
+        5 | 6 => 2,
+        3 | 4 | 9 => 1,
+        _ => 0,
+    };
+    let count = 3 + rng.below(25);
+    let mut ops: Vec<Val> = (0..count)
+        .map(|_| opcodes[rng.below(opcodes.len())])
+        .collect();
+    ops.push(99);
+    let mut boundaries = vec![];
+    let mut len = 0;
+    for &op in &ops {
+        boundaries.push(len as Val);
+        len += 1 + num_params(op);
+    }
+    let data_start = len as Val;
+    let total = (len + DATA_CELLS) as Val;
+
+    let mut program = vec![];
+    for &op in &ops {
+        let mut code = op;
+        let mut params = vec![];
+        for n in 1..=num_params(op) {
+            let writes = matches!((op, n), (1 | 2 | 7 | 8, 3) | (3, 1));
+            let is_target = matches!((op, n), (5 | 6, 2));
+            let mode = match rng.below(10) {
+                _ if writes => [0, 0, 2][rng.below(3)],
+                0..=3 => 1,
+                4..=7 => 0,
+                _ => 2,
+            };
+            let param = match mode {
+                1 if is_target => boundaries[rng.below(boundaries.len())],
+                1 if op == 9 => rng.range(-3, 8),
+                1 => rng.range(-20, 20),
+                0 if writes => data_start + rng.range(0, DATA_CELLS as Val - 1),
+                0 => rng.range(0, total - 1),
+                _ => rng.range(-5, total - 1),
+            };
+            code += mode * [0, 100, 1000, 10000][n];
+            params.push(param);
+        }
+        program.push(code);
+        program.extend(params);
+    }
+    program.extend((0..DATA_CELLS).map(|_| rng.range(-5, 5)));
+    program
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Ran,
+    Output(Val),
+    NeedsInput,
+    Halted,
+    // Something the real computer would panic on (or would never get to).
+    Fault,
+}
+
+// A straightforward interpreter with memory in a map and every check spelled out.
+struct Reference {
+    memory: HashMap<usize, Val>,
+    pos: Val,
+    relative_base: Val,
+    inputs: VecDeque<Val>,
+    outputs: Vec<Val>,
+    // Makes `LT` behave like `<=`, to check the shrinker finds it.
+    buggy: bool,
+}
+
+impl Reference {
+    fn new(program: &[Val], inputs: &[Val]) -> Reference {
+        Reference {
+            memory: program.iter().copied().enumerate().collect(),
+            pos: 0,
+            relative_base: 0,
+            inputs: inputs.iter().copied().collect(),
+            outputs: vec![],
+            buggy: false,
+        }
+    }
+
+    fn read(&self, addr: Val) -> Option<Val> {
+        let addr = usize::try_from(addr).ok()?;
+        Some(self.memory.get(&addr).copied().unwrap_or(0))
+    }
+
+    // Resolves parameter `n`: its value, and the address it names (if not immediate).
+    fn param(&self, code: Val, n: u32) -> Option<(Val, Option<Val>)> {
+        let raw = self.read(self.pos + n as Val)?;
+        match code / 10_i64.pow(n + 1) % 10 {
+            0 => Some((self.read(raw)?, Some(raw))),
+            1 => Some((raw, None)),
+            2 => {
+                let addr = self.relative_base + raw;
+                Some((self.read(addr)?, Some(addr)))
+            }
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, target: Option<Val>, value: Val) -> Option<()> {
+        let addr = usize::try_from(target?).ok()?;
+        self.memory.insert(addr, value);
+        Some(())
+    }
+
+    fn step(&mut self) -> Outcome {
+        self.try_step().unwrap_or(Outcome::Fault)
+    }
+
+    fn try_step(&mut self) -> Option<Outcome> {
+        let code = self.read(self.pos)?;
+        let op = code % 100;
+        let size = match op {
+            1 | 2 | 7 | 8 => 4,
+            5 | 6 => 3,
+            3 | 4 | 9 => 2,
+            99 => 1,
+            _ => return None,
+        };
+        let p = |n| self.param(code, n);
+        let mut next = self.pos + size;
+        let mut outcome = Outcome::Ran;
+        match op {
+            1 => self.write(p(3)?.1, p(1)?.0.checked_add(p(2)?.0)?)?,
+            2 => self.write(p(3)?.1, p(1)?.0.checked_mul(p(2)?.0)?)?,
+            3 => match self.inputs.front() {
+                Some(&input) => {
+                    self.write(p(1)?.1, input)?;
+                    self.inputs.pop_front();
+                }
+                None => return Some(Outcome::NeedsInput),
+            },
+            4 => {
+                let value = p(1)?.0;
+                self.outputs.push(value);
+                outcome = Outcome::Output(value);
+            }
+            5 | 6 => {
+                if (p(1)?.0 != 0) == (op == 5) {
+                    next = p(2)?.0;
+                    usize::try_from(next).ok()?;
+                }
+            }
+            7 => {
+                let (a, b) = (p(1)?.0, p(2)?.0);
+                let less = if self.buggy { a <= b } else { a < b };
+                self.write(p(3)?.1, less as Val)?
+            }
+            8 => self.write(p(3)?.1, (p(1)?.0 == p(2)?.0) as Val)?,
+            9 => self.relative_base = self.relative_base.checked_add(p(1)?.0)?,
+            _ => return Some(Outcome::Halted),
+        }
+        self.pos = next;
+        Some(outcome)
+    }
+
+    fn nonzero_memory(&self) -> BTreeMap<usize, Val> {
+        self.memory
+            .iter()
+            .filter(|(_, &v)| v != 0)
+            .map(|(&a, &v)| (a, v))
+            .collect()
+    }
+}
+
+fn nonzero_memory(comp: &Computer) -> BTreeMap<usize, Val> {
+    let dense = comp.memory.dense().iter().copied().enumerate();
+    let sparse = comp.memory.sparse().iter().map(|(&a, &v)| (a, v));
+    dense.chain(sparse).filter(|&(_, v)| v != 0).collect()
+}
+
+type Checker = fn(&[Val], &[Val]) -> Result<(), String>;
+
+// Steps `Computer` alongside the reference until the program halts, blocks on input, faults
+// or runs too long, comparing outcomes as it goes and the full state at the end. Programs
+// that halt are also checked with translation enabled.
+fn check_against_reference(program: &[Val], inputs: &[Val]) -> Result<(), String> {
+    compare(program, inputs, Reference::new(program, inputs))
+}
+
+fn check_against_buggy_reference(program: &[Val], inputs: &[Val]) -> Result<(), String> {
+    let mut reference = Reference::new(program, inputs);
+    reference.buggy = true;
+    compare(program, inputs, reference)
+}
+
+fn compare(program: &[Val], inputs: &[Val], mut reference: Reference) -> Result<(), String> {
+    let mut comp = Computer::new(program.to_vec());
+    for &input in inputs {
+        comp.set_input(input);
+    }
+    let mut halted = false;
+    for step in 0..MAX_STEPS {
+        let expected = reference.step();
+        if expected == Outcome::Fault {
+            break;
+        }
+        let got = match comp.step() {
+            None => Outcome::Ran,
+            Some(RunState::Output(value)) => Outcome::Output(value),
+            Some(RunState::NeedsInput) => Outcome::NeedsInput,
+            Some(RunState::Halted) => Outcome::Halted,
+        };
+        if got != expected {
+            return Err(format!(
+                "step {}: got {:?}, expected {:?}",
+                step, got, expected
+            ));
+        }
+        if comp.pos as Val != reference.pos || comp.relative_base != reference.relative_base {
+            return Err(format!(
+                "step {}: at {} (rb {}), expected {} (rb {})",
+                step, comp.pos, comp.relative_base, reference.pos, reference.relative_base
+            ));
+        }
+        if got == Outcome::Halted || got == Outcome::NeedsInput {
+            halted = got == Outcome::Halted;
+            break;
+        }
+    }
+    if comp.outputs != reference.outputs {
+        return Err(format!(
+            "outputs {:?}, expected {:?}",
+            comp.outputs, reference.outputs
+        ));
+    }
+    if nonzero_memory(&comp) != reference.nonzero_memory() {
+        return Err("memory differs".to_string());
+    }
+    if halted {
+        let mut translated = Computer::new(program.to_vec());
+        translated.enable_translation();
+        for &input in inputs {
+            translated.set_input(input);
+        }
+        translated.execute();
+        if translated.snapshot() != comp.snapshot() {
+            return Err("translated run differs".to_string());
+        }
+    }
+    Ok(())
+}
+
+// Greedily removes and simplifies words (and inputs) for as long as the check still fails.
+fn shrink(mut program: Vec<Val>, mut inputs: Vec<Val>, check: Checker) -> (Vec<Val>, Vec<Val>) {
+    let fails = |program: &[Val], inputs: &[Val]| check(program, inputs).is_err();
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for size in (1..=4).rev() {
+            let mut i = 0;
+            while i + size <= program.len() {
+                let mut removed = program.clone();
+                removed.drain(i..i + size);
+                // Also try pulling back anything that looks like an address past the gap, so
+                // jumps and pointers still land on the same code.
+                let len = program.len() as Val;
+                let end = (i + size) as Val;
+                let relocated: Vec<Val> = removed
+                    .iter()
+                    .map(|&v| {
+                        if v >= end && v < len {
+                            v - size as Val
+                        } else {
+                            v
+                        }
+                    })
+                    .collect();
+                match [removed, relocated]
+                    .into_iter()
+                    .find(|candidate| fails(candidate, &inputs))
+                {
+                    Some(candidate) => {
+                        program = candidate;
+                        improved = true;
+                    }
+                    None => i += 1,
+                }
+            }
+        }
+        for i in 0..program.len() {
+            for simpler in [0, 1, program[i] / 2] {
+                if simpler.abs() < program[i].abs() {
+                    let mut candidate = program.clone();
+                    candidate[i] = simpler;
+                    if fails(&candidate, &inputs) {
+                        program = candidate;
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+        }
+        while !inputs.is_empty() && fails(&program, &inputs[1..]) {
+            inputs.remove(0);
+            improved = true;
+        }
+    }
+    (program, inputs)
+}
+
+// Runs `count` random programs through `check`, returning the first failure, shrunk.
+fn find_failure(seed: u64, count: usize, check: Checker) -> Option<(Vec<Val>, Vec<Val>, String)> {
+    let mut rng = Rng(seed);
+    for _ in 0..count {
+        let program = random_program(&mut rng);
+        let inputs: Vec<Val> = (0..rng.below(4)).map(|_| rng.range(-10, 10)).collect();
+        if check(&program, &inputs).is_err() {
+            let (program, inputs) = shrink(program, inputs, check);
+            let error = check(&program, &inputs).unwrap_err();
+            return Some((program, inputs, error));
+        }
+    }
+    None
+}
+
+#[test]
+fn test_random_programs_match_reference() {
+    if let Some((program, inputs, error)) = find_failure(0x1ce_c0de, 3000, check_against_reference)
+    {
+        panic!(
+            "Computer disagrees with the reference: {}\nprogram: {:?}\ninputs: {:?}",
+            error, program, inputs
+        );
+    }
+}
+
+#[test]
+fn test_generator_exercises_everything() {
+    let mut rng = Rng(42);
+    let mut halted = 0;
+    let mut opcodes = [0; 100];
+    for _ in 0..500 {
+        let program = random_program(&mut rng);
+        let mut reference = Reference::new(&program, &[1, 2, 3]);
+        for _ in 0..MAX_STEPS {
+            let code = reference.read(reference.pos).unwrap_or(0);
+            match reference.step() {
+                Outcome::Fault | Outcome::NeedsInput => break,
+                Outcome::Halted => {
+                    halted += 1;
+                    break;
+                }
+                _ => opcodes[(code % 100) as usize] += 1,
+            }
+        }
+    }
+    assert!(halted > 50, "only {} programs halted", halted);
+    assert!((1..=9).all(|op| opcodes[op] > 0), "{:?}", &opcodes[..10]);
+}
+
+#[test]
+fn test_shrinks_to_minimal_program() {
+    let (program, inputs, _) = find_failure(7, 3000, check_against_buggy_reference)
+        .expect("the buggy reference should disagree somewhere");
+    // An LT comparing equal values is all it takes.
+    assert!(program.len() <= 4, "{:?}", program);
+    assert_eq!(program[0] % 100, 7);
+    assert!(inputs.is_empty());
+}
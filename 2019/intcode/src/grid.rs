@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::Val;
+
+// x grows to the right and y grows downwards, as on screen.
+pub type Position = (Val, Val);
+
+// A sparse, unbounded 2D plane of values. Cells that were never set hold `default`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Grid {
+    cells: HashMap<Position, Val>,
+    default: Val,
+}
+
+impl Grid {
+    pub fn new(default: Val) -> Grid {
+        Grid {
+            cells: HashMap::new(),
+            default,
+        }
+    }
+
+    pub fn get(&self, pos: Position) -> Val {
+        self.cells.get(&pos).copied().unwrap_or(self.default)
+    }
+
+    pub fn set(&mut self, pos: Position, value: Val) {
+        self.cells.insert(pos, value);
+    }
+
+    // The cells that have been set, even if they were set back to the default.
+    pub fn cells(&self) -> &HashMap<Position, Val> {
+        &self.cells
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn count(&self, value: Val) -> usize {
+        self.cells.values().filter(|&&v| v == value).count()
+    }
+
+    // The smallest and largest corners enclosing every set cell.
+    pub fn bounds(&self) -> Option<(Position, Position)> {
+        let xs = self.cells.keys().map(|&(x, _)| x);
+        let ys = self.cells.keys().map(|&(_, y)| y);
+        Some((
+            (xs.clone().min()?, ys.clone().min()?),
+            (xs.max()?, ys.max()?),
+        ))
+    }
+
+    pub fn render<F: Fn(Val) -> char>(&self, glyph: F) -> String {
+        self.render_with(&glyph, &[])
+    }
+
+    // Renders the set cells plus any `extra` markers, which are drawn over the cells and
+    // widen the area drawn if they fall outside it.
+    pub fn render_with<F: Fn(Val) -> char>(&self, glyph: F, extra: &[(Position, char)]) -> String {
+        let corners = self
+            .bounds()
+            .into_iter()
+            .flat_map(|(min, max)| [min, max])
+            .chain(extra.iter().map(|&(pos, _)| pos));
+        let (mut min, mut max) = ((Val::MAX, Val::MAX), (Val::MIN, Val::MIN));
+        for (x, y) in corners {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        let mut out = String::new();
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
+                let marker = extra.iter().find(|&&(pos, _)| pos == (x, y));
+                out.push(marker.map_or_else(|| glyph(self.get((x, y))), |&(_, c)| c));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid() {
+        let mut grid = Grid::new(0);
+        assert_eq!(grid.bounds(), None);
+        assert_eq!(grid.render(|_| '#'), "");
+        grid.set((2, -1), 1);
+        grid.set((-1, 1), 1);
+        grid.set((0, 0), 0);
+        assert_eq!(grid.get((5, 5)), 0);
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid.count(1), 2);
+        assert_eq!(grid.bounds(), Some(((-1, -1), (2, 1))));
+        let glyph = |v| if v == 1 { '#' } else { '.' };
+        assert_eq!(grid.render(glyph), "...#\n....\n#...\n");
+        assert_eq!(
+            grid.render_with(glyph, &[((3, 0), '@')]),
+            "...#.\n....@\n#....\n"
+        );
+    }
+}
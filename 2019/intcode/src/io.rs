@@ -0,0 +1,43 @@
+use std::collections::VecDeque;
+
+use crate::Val;
+
+// Supplies input values on demand; `None` means no input is available yet.
+pub trait InputSource {
+    fn next_input(&mut self) -> Option<Val>;
+}
+
+// Receives each output value as soon as the program produces it.
+pub trait OutputSink {
+    fn send_output(&mut self, value: Val);
+}
+
+impl<F: FnMut() -> Option<Val>> InputSource for F {
+    fn next_input(&mut self) -> Option<Val> {
+        self()
+    }
+}
+
+impl InputSource for VecDeque<Val> {
+    fn next_input(&mut self) -> Option<Val> {
+        self.pop_front()
+    }
+}
+
+impl<F: FnMut(Val)> OutputSink for F {
+    fn send_output(&mut self, value: Val) {
+        self(value)
+    }
+}
+
+impl OutputSink for Vec<Val> {
+    fn send_output(&mut self, value: Val) {
+        self.push(value)
+    }
+}
+
+impl OutputSink for VecDeque<Val> {
+    fn send_output(&mut self, value: Val) {
+        self.push_back(value)
+    }
+}
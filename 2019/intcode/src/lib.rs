@@ -1,15 +1,47 @@
-#[macro_use]
-extern crate num_derive;
-
-use num::FromPrimitive;
+use std::collections::VecDeque;
+use std::sync::Arc;
 
 use common::InputReader;
 
+mod amplifier;
+mod analysis;
+mod arcade;
+mod ascii;
+#[cfg(test)]
+mod differential;
+mod grid;
+mod io;
+mod memory;
+mod network;
+mod profile;
+mod robot;
+mod snapshot;
+mod translate;
+
+pub use crate::amplifier::{permutations, AmplifierChain, AmplifierMode};
+pub use crate::analysis::{
+    disassemble, Block, ControlFlowGraph, Edge, EdgeKind, Exit, Function, Op, Operand,
+    SelfModification,
+};
+pub use crate::arcade::{Arcade, Joystick, Tile};
+pub use crate::ascii::AsciiOutput;
+pub use crate::grid::{Grid, Position};
+pub use crate::io::{InputSource, OutputSink};
+pub use crate::memory::Memory;
+pub use crate::network::{BusEvent, Network, NetworkState, Packet, PacketBus, NAT_ADDRESS};
+pub use crate::profile::Profile;
+pub use crate::robot::{
+    Body, CellSensor, Heading, MoveAndReport, PaintAndTurn, Protocol, Robot, Sensor, FOUND, MOVED,
+    WALL,
+};
+pub use crate::snapshot::Snapshot;
+pub use crate::translate::Translation;
+
 // Negative values are allowed in intcode programs.
 pub type Val = i64;
 
-#[derive(Debug, Eq, FromPrimitive, PartialEq)]
-enum Instruction {
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Instruction {
     Add = 1,
     Mul = 2,
     Input = 3,
@@ -24,29 +56,50 @@ enum Instruction {
 
 impl From<Val> for Instruction {
     fn from(value: Val) -> Instruction {
-        FromPrimitive::from_i64(value).unwrap_or_else(|| panic!("Unknown Instruction {}", value))
+        Instruction::decode(value).unwrap_or_else(|| panic!("Unknown Instruction {}", value))
     }
 }
 
 impl Instruction {
-    fn num_params(&self) -> usize {
+    pub fn decode(value: Val) -> Option<Instruction> {
+        use Instruction::*;
+        match value {
+            1 => Some(Add),
+            2 => Some(Mul),
+            3 => Some(Input),
+            4 => Some(Output),
+            5 => Some(JumpT),
+            6 => Some(JumpF),
+            7 => Some(LT),
+            8 => Some(EQ),
+            9 => Some(RbOffset),
+            99 => Some(End),
+            _ => None,
+        }
+    }
+
+    // Which parameter (1-based), if any, is written to.
+    pub fn write_param(&self) -> Option<usize> {
         use Instruction::*;
         match self {
-            Add => 3,
-            Mul => 3,
-            Input => 1,
-            Output => 1,
-            JumpT => 2,
-            JumpF => 2,
-            LT => 3,
-            EQ => 3,
-            RbOffset => 1,
+            Add | Mul | LT | EQ => Some(3),
+            Input => Some(1),
+            _ => None,
+        }
+    }
+
+    pub fn num_params(&self) -> usize {
+        use Instruction::*;
+        match self {
+            Add | Mul | LT | EQ => 3,
+            JumpT | JumpF => 2,
+            Input | Output | RbOffset => 1,
             End => 0,
         }
     }
 }
 
-#[derive(Debug, FromPrimitive)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum ParamMode {
     Position = 0,
     Immediate = 1,
@@ -55,167 +108,80 @@ enum ParamMode {
 
 impl From<Val> for ParamMode {
     fn from(value: Val) -> ParamMode {
-        FromPrimitive::from_i64(value).unwrap_or_else(|| panic!("Unknown ParamMode {}", value))
+        ParamMode::decode(value).unwrap_or_else(|| panic!("Unknown ParamMode {}", value))
     }
 }
 
+// Divisors that pick the mode digit for parameters 1, 2 and 3 out of an opcode.
+const MODE_DIVISORS: [Val; 4] = [0, 100, 1000, 10000];
+
 impl ParamMode {
-    fn get_modes(code: Val, num: usize) -> Vec<ParamMode> {
-        let mut remainder = code / 100;
-        (0..num)
-            .map(|_| {
-                let mode = ParamMode::from(remainder % 10);
-                remainder /= 10;
-                mode
-            })
-            .collect()
+    fn decode(value: Val) -> Option<ParamMode> {
+        match value {
+            0 => Some(ParamMode::Position),
+            1 => Some(ParamMode::Immediate),
+            2 => Some(ParamMode::Relative),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn of(code: Val, n: usize) -> ParamMode {
+        ParamMode::from(code / MODE_DIVISORS[n] % 10)
     }
 }
 
-#[derive(Debug)]
-struct Opcode {
-    instruction: Instruction,
-    num_params: usize,
-    param_modes: Vec<ParamMode>,
-    input: Option<Val>,
-    consumed_input: bool,
-    needs_input: bool,
+fn to_address(value: Val) -> usize {
+    usize::try_from(value).unwrap_or_else(|_| panic!("Negative address {}", value))
 }
 
-impl From<Val> for Opcode {
-    fn from(code: Val) -> Opcode {
-        let instr = Instruction::from(code % 100);
-        let num_params = instr.num_params();
-        let param_modes = ParamMode::get_modes(code, num_params);
-        Opcode {
-            instruction: instr,
-            num_params,
-            param_modes,
-            input: None,
-            consumed_input: false,
-            needs_input: false,
-        }
-    }
+type Program = Vec<Val>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RunState {
+    NeedsInput,
+    Output(Val),
+    Halted,
 }
 
-impl Opcode {
-    fn act(&mut self, pos: &usize, rb: &Val, program: &mut Program) -> (usize, Val, Option<Val>) {
-        use Instruction::*;
-        let mut idxs = vec![];
-        let mut params = vec![];
-        for n in 0..self.num_params {
-            let param_val = program[pos + 1 + n];
-            let (idx, value) = match self.param_modes[n] {
-                ParamMode::Position => {
-                    let idx = param_val as usize;
-                    let value = if param_val < program.len() as Val {
-                        program[idx]
-                    } else {
-                        0
-                    };
-                    (Some(idx), value)
-                }
-                ParamMode::Immediate => (None, param_val),
-                ParamMode::Relative => {
-                    let idx = param_val + *rb;
-                    let value = if idx < program.len() as Val {
-                        program[idx as usize]
-                    } else {
-                        0
-                    };
-                    (Some(idx as usize), value)
-                }
-            };
-            idxs.push(idx);
-            params.push(value);
-        }
-        let k = if self.num_params >= 3 {
-            idxs[2].unwrap()
-        } else {
-            0
-        };
-        if k >= program.len() {
-            program.resize(k + 1, 0);
-        }
-        let mut new_pos = pos + 1 + self.num_params;
-        let mut new_rb = *rb;
-        let mut output = None;
-        match self.instruction {
-            Add => program[k] = params[0] + params[1],
-            Mul => program[k] = params[0] * params[1],
-            Input => {
-                self.needs_input = true;
-                if let Some(input) = self.input {
-                    program[idxs[0].unwrap()] = input;
-                    self.consumed_input = true;
-                    self.needs_input = false;
-                }
-            }
-            Output => {
-                output = Some(params[0]);
-            }
-            JumpT => {
-                if params[0] != 0 {
-                    new_pos = params[1] as usize;
-                }
-            }
-            JumpF => {
-                if params[0] == 0 {
-                    new_pos = params[1] as usize;
-                }
-            }
-            LT => {
-                if params[0] < params[1] {
-                    program[k] = 1;
-                } else {
-                    program[k] = 0;
-                }
-            }
-            EQ => {
-                if params[0] == params[1] {
-                    program[k] = 1;
-                } else {
-                    program[k] = 0;
-                }
-            }
-            RbOffset => {
-                new_rb += params[0];
-            }
-            End => {}
-        };
-        (new_pos, new_rb, output)
-    }
+pub struct Outputs<'a> {
+    computer: &'a mut Computer,
+}
 
-    fn end(&self) -> bool {
-        self.instruction == Instruction::End
-    }
+impl<'a> Iterator for Outputs<'a> {
+    type Item = Val;
 
-    fn set_possible_input(&mut self, input: Val) {
-        self.input = Some(input)
+    fn next(&mut self) -> Option<Val> {
+        match self.computer.run_until_output() {
+            RunState::Output(value) => Some(value),
+            _ => None,
+        }
     }
 }
 
-type Program = Vec<Val>;
-
 #[derive(Clone)]
 pub struct Computer {
-    program: Program,
+    memory: Memory,
     pos: usize,
     relative_base: Val,
-    inputs: Vec<Val>,
+    inputs: VecDeque<Val>,
     outputs: Vec<Val>,
     finished: bool,
+    profile: Option<Box<Profile>>,
+    translation: Option<Arc<Translation>>,
 }
 
 impl Computer {
     pub fn new(program: Program) -> Computer {
         Computer {
-            program,
+            memory: Memory::new(program),
             pos: 0,
             relative_base: 0,
-            inputs: vec![],
+            inputs: VecDeque::new(),
             outputs: vec![],
             finished: false,
+            profile: None,
+            translation: None,
         }
     }
 
@@ -224,39 +190,178 @@ impl Computer {
     }
 
     pub fn set_noun_verb(&mut self, noun: Val, verb: Val) {
-        self.program[1] = noun;
-        self.program[2] = verb;
+        self.write(1, noun);
+        self.write(2, verb);
     }
 
     pub fn set_input(&mut self, input: Val) {
-        self.inputs.push(input);
+        self.inputs.push_back(input);
     }
 
     pub fn execute(&mut self) -> Val {
+        while let RunState::Output(_) = self.run_until_output() {}
+        self.memory.get(0)
+    }
+
+    // Address that parameter `n` (1-based) of the current instruction refers to.
+    #[inline]
+    fn address(&self, code: Val, n: usize) -> usize {
+        let param = self.memory.get(self.pos + n);
+        match ParamMode::of(code, n) {
+            ParamMode::Position => to_address(param),
+            ParamMode::Relative => to_address(self.relative_base + param),
+            ParamMode::Immediate => panic!("Immediate mode parameter used as an address"),
+        }
+    }
+
+    // Starts counting instruction executions and memory use; see `profile`.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Box::new(Profile::new(self.memory.dense_len())));
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    #[inline]
+    fn param(&self, code: Val, n: usize) -> Val {
+        let param = self.memory.get(self.pos + n);
+        match ParamMode::of(code, n) {
+            ParamMode::Position => self.memory.get(to_address(param)),
+            ParamMode::Immediate => param,
+            ParamMode::Relative => self.memory.get(to_address(self.relative_base + param)),
+        }
+    }
+
+    // Runs until the next output is produced, more input is needed, or the program ends.
+    pub fn run_until_output(&mut self) -> RunState {
+        if self.finished {
+            return RunState::Halted;
+        }
+        if self.translation.is_some() && self.profile.is_none() {
+            return self.run_translated();
+        }
         loop {
-            let mut opcode = Opcode::from(self.program[self.pos]);
-            if opcode.end() {
-                self.finished = true;
-                break;
+            if let Some(state) = self.step() {
+                return state;
+            }
+        }
+    }
+
+    // Interprets one instruction straight from memory, returning a state if execution
+    // should pause.
+    #[inline]
+    fn step(&mut self) -> Option<RunState> {
+        use Instruction::*;
+        let code = self.memory.get(self.pos);
+        let instruction = Instruction::from(code % 100);
+        if let Some(profile) = self.profile.as_mut() {
+            // Blocked inputs are retried later, so only count them once they run.
+            if instruction != Input || !self.inputs.is_empty() {
+                profile.record(
+                    instruction,
+                    code,
+                    self.pos,
+                    self.relative_base,
+                    &self.memory,
+                );
+            }
+        }
+        match instruction {
+            Add => {
+                let value = self.param(code, 1) + self.param(code, 2);
+                self.write(self.address(code, 3), value);
+                self.pos += 4;
             }
-            if !self.inputs.is_empty() {
-                opcode.set_possible_input(self.inputs[0]);
+            Mul => {
+                let value = self.param(code, 1) * self.param(code, 2);
+                self.write(self.address(code, 3), value);
+                self.pos += 4;
             }
-            let (new_pos, new_rb, output) =
-                opcode.act(&self.pos, &self.relative_base, &mut self.program);
-            if let Some(out) = output {
+            Input => match self.inputs.pop_front() {
+                Some(input) => {
+                    self.write(self.address(code, 1), input);
+                    self.pos += 2;
+                }
+                None => return Some(RunState::NeedsInput),
+            },
+            Output => {
+                let out = self.param(code, 1);
+                self.pos += 2;
                 self.outputs.push(out);
+                return Some(RunState::Output(out));
+            }
+            JumpT => {
+                if self.param(code, 1) != 0 {
+                    self.pos = to_address(self.param(code, 2));
+                } else {
+                    self.pos += 3;
+                }
+            }
+            JumpF => {
+                if self.param(code, 1) == 0 {
+                    self.pos = to_address(self.param(code, 2));
+                } else {
+                    self.pos += 3;
+                }
+            }
+            LT => {
+                let value = (self.param(code, 1) < self.param(code, 2)) as Val;
+                self.write(self.address(code, 3), value);
+                self.pos += 4;
+            }
+            EQ => {
+                let value = (self.param(code, 1) == self.param(code, 2)) as Val;
+                self.write(self.address(code, 3), value);
+                self.pos += 4;
+            }
+            RbOffset => {
+                self.relative_base += self.param(code, 1);
+                self.pos += 2;
+            }
+            End => {
+                self.finished = true;
+                return Some(RunState::Halted);
             }
-            if opcode.consumed_input {
-                self.inputs.remove(0);
+        }
+        None
+    }
+
+    // Writes to memory, discarding any translation the write would make stale.
+    #[inline]
+    fn write(&mut self, addr: usize, value: Val) {
+        if let Some(translation) = &self.translation {
+            if translation.is_code(addr) {
+                self.translation = None;
             }
-            if opcode.needs_input {
-                break;
+        }
+        self.memory.set(addr, value);
+    }
+
+    // Runs until halted, pulling inputs from `input` whenever the queue runs dry and pushing
+    // each output to `output` as soon as it is produced. Returns `NeedsInput` if the source
+    // runs out before the program ends.
+    pub fn run_with<I, O>(&mut self, input: &mut I, output: &mut O) -> RunState
+    where
+        I: InputSource + ?Sized,
+        O: OutputSink + ?Sized,
+    {
+        loop {
+            match self.run_until_output() {
+                RunState::Output(value) => output.send_output(value),
+                RunState::NeedsInput => match input.next_input() {
+                    Some(value) => self.set_input(value),
+                    None => return RunState::NeedsInput,
+                },
+                RunState::Halted => return RunState::Halted,
             }
-            self.pos = new_pos;
-            self.relative_base = new_rb;
         }
-        self.program[0]
+    }
+
+    // Iterates over outputs as they are produced, stopping when input is needed or the
+    // program ends.
+    pub fn outputs(&mut self) -> Outputs<'_> {
+        Outputs { computer: self }
     }
 
     pub fn all_outputs(&self) -> &Vec<Val> {
@@ -275,6 +380,8 @@ impl Computer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
+    use std::collections::VecDeque;
 
     #[test]
     fn test_day02_examples() {
@@ -330,7 +437,7 @@ mod tests {
         // Produces a 16-digit number.
         let mut comp2 = Computer::new(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]);
         comp2.execute();
-        let comp2_out = comp2.final_output().unwrap().clone();
+        let comp2_out = *comp2.final_output().unwrap();
         assert!(comp2_out >= (10 as Val).pow(15) && comp2_out < (10 as Val).pow(16));
 
         // Produces the large value in the middle.
@@ -338,4 +445,56 @@ mod tests {
         comp3.execute();
         assert_eq!(comp3.final_output(), Some(&1125899906842624));
     }
+
+    #[test]
+    fn test_run_until_output() {
+        // Echoes two inputs, then halts.
+        let mut comp = Computer::new(vec![3, 9, 4, 9, 3, 9, 4, 9, 99, 0]);
+        assert_eq!(comp.run_until_output(), RunState::NeedsInput);
+        comp.set_input(7);
+        assert_eq!(comp.run_until_output(), RunState::Output(7));
+        assert_eq!(comp.run_until_output(), RunState::NeedsInput);
+        comp.set_input(8);
+        assert_eq!(comp.run_until_output(), RunState::Output(8));
+        assert_eq!(comp.run_until_output(), RunState::Halted);
+        assert!(comp.is_finished());
+        assert_eq!(comp.all_outputs(), &vec![7, 8]);
+    }
+
+    #[test]
+    fn test_outputs_iterator() {
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut comp = Computer::new(quine.clone());
+        let outputs: Vec<Val> = comp.outputs().collect();
+        assert_eq!(outputs, quine);
+        assert_eq!(comp.outputs().next(), None);
+    }
+
+    #[test]
+    fn test_run_with() {
+        // Doubles each input forever.
+        let program = vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0];
+        let mut comp = Computer::new(program);
+        let mut inputs = VecDeque::from(vec![1, 2, 3]);
+        let mut outputs = vec![];
+        assert_eq!(
+            comp.run_with(&mut inputs, &mut outputs),
+            RunState::NeedsInput
+        );
+        assert_eq!(outputs, vec![2, 4, 6]);
+
+        // Closures can react to each output.
+        let last = Cell::new(1);
+        let mut seen = vec![];
+        comp.run_with(
+            &mut || Some(last.get()).filter(|&v| v < 100),
+            &mut |value| {
+                last.set(value);
+                seen.push(value);
+            },
+        );
+        assert_eq!(seen, vec![2, 4, 8, 16, 32, 64, 128]);
+    }
 }
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use crate::Val;
+
+// Writes this far past the end of the dense region go to the sparse map instead of growing it.
+pub(crate) const MAX_GROWTH: usize = 4096;
+
+// Intcode memory: the program plus any nearby addresses live in a dense `Vec`, while writes to
+// far-away addresses are kept in a map so they don't force a huge allocation. Unwritten
+// addresses read as 0.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Memory {
+    dense: Vec<Val>,
+    sparse: HashMap<usize, Val>,
+}
+
+impl Memory {
+    pub fn new(program: Vec<Val>) -> Memory {
+        Memory {
+            dense: program,
+            sparse: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn from_parts(dense: Vec<Val>, sparse: HashMap<usize, Val>) -> Memory {
+        Memory { dense, sparse }
+    }
+
+    #[inline]
+    pub fn get(&self, addr: usize) -> Val {
+        match self.dense.get(addr) {
+            Some(value) => *value,
+            None => self.sparse.get(&addr).copied().unwrap_or(0),
+        }
+    }
+
+    #[inline]
+    pub fn set(&mut self, addr: usize, value: Val) {
+        if addr < self.dense.len() {
+            self.dense[addr] = value;
+        } else if addr < self.dense.len() + MAX_GROWTH {
+            self.dense.resize(addr + 1, 0);
+            // Anything previously stored sparsely in the newly dense range moves over.
+            if !self.sparse.is_empty() {
+                let dense = &mut self.dense;
+                self.sparse.retain(|&a, v| {
+                    if a < dense.len() {
+                        dense[a] = *v;
+                        false
+                    } else {
+                        true
+                    }
+                });
+            }
+            self.dense[addr] = value;
+        } else {
+            self.sparse.insert(addr, value);
+        }
+    }
+
+    // Number of addresses held densely; everything at or beyond this is sparse.
+    pub fn dense_len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn dense(&self) -> &[Val] {
+        &self.dense
+    }
+
+    pub fn sparse(&self) -> &HashMap<usize, Val> {
+        &self.sparse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dense_and_sparse() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        assert_eq!(memory.get(1), 2);
+        assert_eq!(memory.get(10), 0);
+
+        memory.set(10, 7);
+        assert_eq!(memory.dense_len(), 11);
+        assert_eq!(memory.get(10), 7);
+
+        memory.set(1_000_000_000, 8);
+        assert_eq!(memory.dense_len(), 11);
+        assert_eq!(memory.get(1_000_000_000), 8);
+        assert_eq!(memory.sparse().len(), 1);
+    }
+
+    #[test]
+    fn test_sparse_moves_to_dense() {
+        let mut memory = Memory::new(vec![0; 4]);
+        memory.set(5000, 1);
+        assert_eq!(memory.dense_len(), 4);
+        memory.set(2000, 2);
+        memory.set(4000, 3);
+        memory.set(6000, 4);
+        assert_eq!(memory.get(5000), 1);
+        assert_eq!(memory.dense_len(), 6001);
+        assert!(memory.sparse().is_empty());
+    }
+}
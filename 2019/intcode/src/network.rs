@@ -0,0 +1,340 @@
+use std::collections::VecDeque;
+
+use crate::{Computer, RunState, Val};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NetworkState {
+    // Every live computer is waiting for input that nobody is going to send.
+    Quiescent,
+    Halted,
+}
+
+// Computers wired together so that each one's outputs become another's inputs. Outputs from
+// a node without a route are collected as the network's external outputs.
+pub struct Network {
+    nodes: Vec<Computer>,
+    routes: Vec<Option<usize>>,
+    external: Vec<Val>,
+}
+
+impl Network {
+    pub fn new(nodes: Vec<Computer>) -> Network {
+        let routes = vec![None; nodes.len()];
+        Network {
+            nodes,
+            routes,
+            external: vec![],
+        }
+    }
+
+    // Each computer feeds the next; the last one's outputs are external.
+    pub fn chain(nodes: Vec<Computer>) -> Network {
+        let mut network = Network::new(nodes);
+        for i in 1..network.len() {
+            network.connect(i - 1, i);
+        }
+        network
+    }
+
+    // Like a chain, but the last computer feeds back into the first.
+    pub fn feedback_loop(nodes: Vec<Computer>) -> Network {
+        let mut network = Network::chain(nodes);
+        if !network.is_empty() {
+            network.connect(network.len() - 1, 0);
+        }
+        network
+    }
+
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.routes[from] = Some(to);
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, i: usize) -> &Computer {
+        &self.nodes[i]
+    }
+
+    pub fn send(&mut self, to: usize, value: Val) {
+        self.nodes[to].set_input(value);
+    }
+
+    pub fn external_outputs(&self) -> &Vec<Val> {
+        &self.external
+    }
+
+    // Round-robin over the computers, letting each run until it blocks on input or halts,
+    // until nothing is left to do.
+    pub fn run(&mut self) -> NetworkState {
+        loop {
+            let mut progress = false;
+            for i in 0..self.nodes.len() {
+                if self.nodes[i].is_finished() {
+                    continue;
+                }
+                progress |= !self.nodes[i].inputs.is_empty();
+                loop {
+                    match self.nodes[i].run_until_output() {
+                        RunState::Output(value) => {
+                            progress = true;
+                            match self.routes[i] {
+                                Some(to) => self.nodes[to].set_input(value),
+                                None => self.external.push(value),
+                            }
+                        }
+                        RunState::NeedsInput => break,
+                        RunState::Halted => {
+                            progress = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            if self.nodes.iter().all(|n| n.is_finished()) {
+                return NetworkState::Halted;
+            }
+            if !progress {
+                return NetworkState::Quiescent;
+            }
+        }
+    }
+}
+
+pub const NAT_ADDRESS: Val = 255;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Packet {
+    pub dest: Val,
+    pub x: Val,
+    pub y: Val,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BusEvent {
+    // A packet addressed to the NAT.
+    NatReceived(Packet),
+    // The network went idle, so the NAT sent its last packet to address 0.
+    NatWake(Packet),
+    // A packet addressed to no computer on the bus, which was dropped.
+    Undeliverable(Packet),
+}
+
+// Computers that each boot with their own address, then exchange `(dest, x, y)` packets.
+// Computers that ask for input with nothing queued receive -1. Packets sent to address 255
+// are held by the NAT, which resends the latest one to address 0 whenever the whole network
+// is idle.
+pub struct PacketBus {
+    nodes: Vec<Computer>,
+    queues: Vec<VecDeque<Val>>,
+    partial: Vec<Vec<Val>>,
+    nat: Option<Packet>,
+}
+
+impl PacketBus {
+    pub fn new(computer: &Computer, size: usize) -> PacketBus {
+        let nodes = (0..size)
+            .map(|addr| {
+                let mut node = computer.clone();
+                node.set_input(addr as Val);
+                node
+            })
+            .collect();
+        PacketBus {
+            nodes,
+            queues: vec![VecDeque::new(); size],
+            partial: vec![vec![]; size],
+            nat: None,
+        }
+    }
+
+    // Runs the network, reporting NAT activity to `monitor` until it returns `false`. Returns
+    // the event that stopped the run, or `None` if every computer halted (or the network went
+    // idle with nothing for the NAT to send).
+    pub fn run<F>(&mut self, mut monitor: F) -> Option<BusEvent>
+    where
+        F: FnMut(&BusEvent) -> bool,
+    {
+        loop {
+            let mut idle = true;
+            for i in 0..self.nodes.len() {
+                if self.nodes[i].is_finished() {
+                    continue;
+                }
+                if self.queues[i].is_empty() {
+                    self.nodes[i].set_input(-1);
+                } else {
+                    idle = false;
+                    for value in self.queues[i].drain(..) {
+                        self.nodes[i].set_input(value);
+                    }
+                }
+                while let RunState::Output(value) = self.nodes[i].run_until_output() {
+                    idle = false;
+                    self.partial[i].push(value);
+                    if self.partial[i].len() < 3 {
+                        continue;
+                    }
+                    let packet = Packet {
+                        dest: self.partial[i][0],
+                        x: self.partial[i][1],
+                        y: self.partial[i][2],
+                    };
+                    self.partial[i].clear();
+                    if let Some(event) = self.route(packet) {
+                        if !monitor(&event) {
+                            return Some(event);
+                        }
+                    }
+                }
+            }
+            if self.nodes.iter().all(|n| n.is_finished()) {
+                return None;
+            }
+            if idle {
+                let packet = self.nat?;
+                self.queues[0].extend([packet.x, packet.y]);
+                let event = BusEvent::NatWake(packet);
+                if !monitor(&event) {
+                    return Some(event);
+                }
+            }
+        }
+    }
+
+    fn route(&mut self, packet: Packet) -> Option<BusEvent> {
+        if packet.dest == NAT_ADDRESS {
+            self.nat = Some(packet);
+            return Some(BusEvent::NatReceived(packet));
+        }
+        // the address comes from the guest program, so it may be anything
+        let queue = usize::try_from(packet.dest)
+            .ok()
+            .and_then(|dest| self.queues.get_mut(dest));
+        match queue {
+            Some(queue) => {
+                queue.push_back(packet.x);
+                queue.push_back(packet.y);
+                None
+            }
+            None => Some(BusEvent::Undeliverable(packet)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads its address, reports (address, address * 10) to the NAT, then reads forever.
+    fn reporting_node() -> Computer {
+        let mut program = vec![
+            3, 50, 104, 255, 4, 50, 1002, 50, 10, 51, 4, 51, 3, 52, 1105, 1, 12,
+        ];
+        program.resize(53, 0);
+        Computer::new(program)
+    }
+
+    #[test]
+    fn test_chain() {
+        // Adds its two inputs.
+        let adder = Computer::new(vec![3, 12, 3, 13, 1, 12, 13, 14, 4, 14, 99, 0, 0, 0, 0]);
+        let mut network = Network::chain(vec![adder.clone(), adder.clone(), adder]);
+        network.send(0, 1);
+        network.send(0, 2);
+        network.send(1, 10);
+        network.send(2, 100);
+        assert_eq!(network.run(), NetworkState::Halted);
+        assert_eq!(network.external_outputs(), &vec![113]);
+    }
+
+    #[test]
+    fn test_quiescent() {
+        let adder = Computer::new(vec![3, 12, 3, 13, 1, 12, 13, 14, 4, 14, 99, 0, 0, 0, 0]);
+        let mut network = Network::chain(vec![adder.clone(), adder]);
+        network.send(0, 1);
+        network.send(0, 2);
+        assert_eq!(network.run(), NetworkState::Quiescent);
+        network.send(1, 3);
+        assert_eq!(network.run(), NetworkState::Halted);
+        assert_eq!(network.external_outputs(), &vec![6]);
+    }
+
+    #[test]
+    fn test_feedback_loop() {
+        // 2019 day 7 example with phases 9,8,7,6,5.
+        let amp = Computer::new(vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ]);
+        let mut network = Network::feedback_loop(vec![amp; 5]);
+        for (i, phase) in [9, 8, 7, 6, 5].into_iter().enumerate() {
+            network.send(i, phase);
+        }
+        network.send(0, 0);
+        assert_eq!(network.run(), NetworkState::Halted);
+        assert_eq!(network.node(4).final_output(), Some(&139629729));
+    }
+
+    #[test]
+    fn test_packet_bus() {
+        let mut bus = PacketBus::new(&reporting_node(), 3);
+        let first = bus.run(|event| !matches!(event, BusEvent::NatReceived(_)));
+        assert_eq!(
+            first,
+            Some(BusEvent::NatReceived(Packet {
+                dest: 255,
+                x: 0,
+                y: 0
+            }))
+        );
+
+        let mut bus = PacketBus::new(&reporting_node(), 3);
+        let mut last_wake = None;
+        let repeated = bus.run(|event| match event {
+            BusEvent::NatWake(packet) => last_wake.replace(packet.y) != Some(packet.y),
+            _ => true,
+        });
+        assert_eq!(
+            repeated,
+            Some(BusEvent::NatWake(Packet {
+                dest: 255,
+                x: 2,
+                y: 20
+            }))
+        );
+    }
+
+    #[test]
+    fn test_undeliverable_packets() {
+        // Reads its address, sends (1, 2) to -5 and (3, 4) to 7, then reads forever.
+        let mut program = vec![
+            3, 50, 104, -5, 104, 1, 104, 2, 104, 7, 104, 3, 104, 4, 3, 50, 1105, 1, 14,
+        ];
+        program.resize(51, 0);
+        let mut bus = PacketBus::new(&Computer::new(program), 3);
+        let mut dropped = vec![];
+        let event = bus.run(|event| match event {
+            BusEvent::Undeliverable(packet) => {
+                dropped.push(packet.dest);
+                dropped.len() < 6
+            }
+            _ => true,
+        });
+        assert_eq!(
+            event,
+            Some(BusEvent::Undeliverable(Packet {
+                dest: 7,
+                x: 3,
+                y: 4
+            }))
+        );
+        // every computer sends both, and the bus keeps going
+        assert_eq!(dropped, vec![-5, 7, -5, 7, -5, 7]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{to_address, Instruction, Memory, ParamMode, Val};
+
+// How many hot spots the `Display` report lists.
+const REPORT_HOT_SPOTS: usize = 10;
+
+// Execution statistics gathered while a `Computer` runs with profiling enabled.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub steps: u64,
+    pub by_address: HashMap<usize, u64>,
+    pub by_instruction: HashMap<Instruction, u64>,
+    pub program_len: usize,
+    pub max_address: usize,
+    pub min_relative_base: Val,
+    pub max_relative_base: Val,
+}
+
+impl Profile {
+    pub(crate) fn new(program_len: usize) -> Profile {
+        Profile {
+            program_len,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        instruction: Instruction,
+        code: Val,
+        pos: usize,
+        relative_base: Val,
+        memory: &Memory,
+    ) {
+        self.steps += 1;
+        *self.by_address.entry(pos).or_insert(0) += 1;
+        *self.by_instruction.entry(instruction).or_insert(0) += 1;
+        self.min_relative_base = self.min_relative_base.min(relative_base);
+        self.max_relative_base = self.max_relative_base.max(relative_base);
+        let num_params = instruction.num_params();
+        self.max_address = self.max_address.max(pos + num_params);
+        for n in 1..=num_params {
+            let param = memory.get(pos + n);
+            let addr = match ParamMode::of(code, n) {
+                ParamMode::Position => to_address(param),
+                ParamMode::Relative => to_address(relative_base + param),
+                ParamMode::Immediate => continue,
+            };
+            self.max_address = self.max_address.max(addr);
+        }
+    }
+
+    // The `top` most executed addresses, busiest first.
+    pub fn hot_spots(&self, top: usize) -> Vec<(usize, u64)> {
+        let mut spots: Vec<(usize, u64)> = self.by_address.iter().map(|(&a, &c)| (a, c)).collect();
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots.truncate(top);
+        spots
+    }
+
+    pub fn report(&self, top: usize) -> String {
+        let pct = |count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+        let mut out = String::new();
+        out.push_str(&format!("Executed {} instructions\n", self.steps));
+        out.push_str(&format!(
+            "Max address touched: {} (program length {})\n",
+            self.max_address, self.program_len
+        ));
+        out.push_str(&format!(
+            "Relative base range: {}..={}\n",
+            self.min_relative_base, self.max_relative_base
+        ));
+        out.push_str("By instruction:\n");
+        let mut instructions: Vec<(&Instruction, &u64)> = self.by_instruction.iter().collect();
+        instructions.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (instruction, &count) in instructions {
+            out.push_str(&format!(
+                "  {:<10} {:>12} {:>6.2}%\n",
+                format!("{:?}", instruction),
+                count,
+                pct(count)
+            ));
+        }
+        out.push_str("Hot spots:\n");
+        for (addr, count) in self.hot_spots(top) {
+            out.push_str(&format!(
+                "  @{:<9} {:>12} {:>6.2}%\n",
+                addr,
+                count,
+                pct(count)
+            ));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.report(REPORT_HOT_SPOTS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Computer, Instruction, RunState};
+
+    #[test]
+    fn test_profile_counts() {
+        // Counts down from the input, writing far past the end of the program each time.
+        let mut comp = Computer::new(vec![
+            3, 100, 109, 7, 21101, 0, 0, 1000, 1001, 100, -1, 100, 1005, 100, 4, 99,
+        ]);
+        assert!(comp.profile().is_none());
+        comp.enable_profiling();
+        assert_eq!(comp.run_until_output(), RunState::NeedsInput);
+        assert_eq!(comp.profile().unwrap().steps, 0);
+        comp.set_input(5);
+        comp.execute();
+
+        let profile = comp.profile().unwrap();
+        assert_eq!(profile.steps, 2 + 5 * 3 + 1);
+        assert_eq!(profile.by_instruction[&Instruction::Input], 1);
+        assert_eq!(profile.by_instruction[&Instruction::Add], 10);
+        assert_eq!(profile.by_instruction[&Instruction::JumpT], 5);
+        assert_eq!(profile.by_address[&4], 5);
+        assert_eq!(profile.max_address, 1007);
+        assert_eq!(profile.max_relative_base, 7);
+        assert_eq!(profile.hot_spots(1), vec![(4, 5)]);
+
+        let report = profile.to_string();
+        assert!(report.starts_with("Executed 18 instructions\n"));
+        assert!(report.contains("Max address touched: 1007 (program length 16)"));
+    }
+}
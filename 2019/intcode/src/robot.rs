@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+
+use crate::{Computer, Grid, Position, RunState, Val};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Heading {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Heading {
+    pub fn turn_left(self) -> Heading {
+        match self {
+            Heading::Up => Heading::Left,
+            Heading::Left => Heading::Down,
+            Heading::Down => Heading::Right,
+            Heading::Right => Heading::Up,
+        }
+    }
+
+    pub fn turn_right(self) -> Heading {
+        match self {
+            Heading::Up => Heading::Right,
+            Heading::Right => Heading::Down,
+            Heading::Down => Heading::Left,
+            Heading::Left => Heading::Up,
+        }
+    }
+
+    // The position one step ahead of `pos`.
+    pub fn step(self, (x, y): Position) -> Position {
+        match self {
+            Heading::Up => (x, y - 1),
+            Heading::Right => (x + 1, y),
+            Heading::Down => (x, y + 1),
+            Heading::Left => (x - 1, y),
+        }
+    }
+
+    pub fn glyph(self) -> char {
+        match self {
+            Heading::Up => '^',
+            Heading::Right => '>',
+            Heading::Down => 'v',
+            Heading::Left => '<',
+        }
+    }
+}
+
+// Decides what the robot's program reads as input each step.
+pub trait Sensor {
+    fn sense(&mut self, world: &Grid, position: Position, heading: Heading) -> Val;
+}
+
+// Reports the value of the cell the robot is standing on.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CellSensor;
+
+impl Sensor for CellSensor {
+    fn sense(&mut self, world: &Grid, position: Position, _heading: Heading) -> Val {
+        world.get(position)
+    }
+}
+
+impl<F: FnMut(&Grid, Position, Heading) -> Val> Sensor for F {
+    fn sense(&mut self, world: &Grid, position: Position, heading: Heading) -> Val {
+        self(world, position, heading)
+    }
+}
+
+// What a robot knows about the world, which its protocol updates as the program replies.
+pub struct Body {
+    pub world: Grid,
+    pub position: Position,
+    pub heading: Heading,
+    // Every cell written to at least once.
+    pub painted: HashSet<Position>,
+}
+
+impl Body {
+    pub fn write(&mut self, position: Position, value: Val) {
+        self.world.set(position, value);
+        self.painted.insert(position);
+    }
+}
+
+// How a robot's program answers each input, and what the robot does about it.
+pub trait Protocol {
+    // The value of cells nobody has written to yet.
+    fn unknown(&self) -> Val {
+        0
+    }
+
+    // Reads the program's reply to `input` and updates `body` to match. Returns false if
+    // the program halted instead.
+    fn respond(&mut self, computer: &mut Computer, input: Val, body: &mut Body) -> bool;
+}
+
+// The painting robot protocol: the program answers with the value to write to the current
+// cell, then which way to turn (0 for left, 1 for right) before moving forward one cell.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PaintAndTurn;
+
+impl Protocol for PaintAndTurn {
+    fn respond(&mut self, computer: &mut Computer, _input: Val, body: &mut Body) -> bool {
+        let value = match computer.run_until_output() {
+            RunState::Output(value) => value,
+            RunState::Halted => return false,
+            RunState::NeedsInput => panic!("robot program asked for a second reading"),
+        };
+        let turn = match computer.run_until_output() {
+            RunState::Output(turn) => turn,
+            state => panic!("robot program stopped mid-step: {:?}", state),
+        };
+        body.write(body.position, value);
+        body.heading = match turn {
+            0 => body.heading.turn_left(),
+            1 => body.heading.turn_right(),
+            _ => panic!("unknown turn: {}", turn),
+        };
+        body.position = body.heading.step(body.position);
+        true
+    }
+}
+
+// The exploring robot protocol: the input is a move command (1 north, 2 south, 3 west,
+// 4 east), and the program answers with a status: 0 if there's a wall in the way, 1 if the
+// robot moved, or 2 if it moved and found what it's looking for. The status is written to
+// the cell the robot tried to move into; unexplored cells are -1.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MoveAndReport;
+
+pub const WALL: Val = 0;
+pub const MOVED: Val = 1;
+pub const FOUND: Val = 2;
+
+impl MoveAndReport {
+    pub fn heading(command: Val) -> Heading {
+        match command {
+            1 => Heading::Up,
+            2 => Heading::Down,
+            3 => Heading::Left,
+            4 => Heading::Right,
+            _ => panic!("unknown move command: {}", command),
+        }
+    }
+
+    pub fn command(heading: Heading) -> Val {
+        match heading {
+            Heading::Up => 1,
+            Heading::Down => 2,
+            Heading::Left => 3,
+            Heading::Right => 4,
+        }
+    }
+}
+
+impl Protocol for MoveAndReport {
+    fn unknown(&self) -> Val {
+        -1
+    }
+
+    fn respond(&mut self, computer: &mut Computer, input: Val, body: &mut Body) -> bool {
+        let status = match computer.run_until_output() {
+            RunState::Output(status) => status,
+            RunState::Halted => return false,
+            RunState::NeedsInput => panic!("robot program asked for a second command"),
+        };
+        body.heading = MoveAndReport::heading(input);
+        let target = body.heading.step(body.position);
+        body.write(target, status);
+        match status {
+            WALL => {}
+            MOVED | FOUND => body.position = target,
+            _ => panic!("unknown status: {}", status),
+        }
+        true
+    }
+}
+
+// A robot driven by an intcode program. Each step the sensor decides the input, and the
+// protocol handles the program's reply; by default that's the painting robot, which is sent
+// the value of the cell it's on.
+pub struct Robot<S = CellSensor, P = PaintAndTurn> {
+    computer: Computer,
+    sensor: S,
+    protocol: P,
+    body: Body,
+    steps: usize,
+}
+
+impl Robot<CellSensor> {
+    pub fn new(computer: Computer) -> Robot<CellSensor> {
+        Robot::with_sensor(computer, CellSensor)
+    }
+}
+
+impl<S: Sensor> Robot<S> {
+    pub fn with_sensor(computer: Computer, sensor: S) -> Robot<S> {
+        Robot::with_protocol(computer, sensor, PaintAndTurn)
+    }
+}
+
+impl<S: Sensor, P: Protocol> Robot<S, P> {
+    pub fn with_protocol(computer: Computer, sensor: S, protocol: P) -> Robot<S, P> {
+        Robot {
+            computer,
+            sensor,
+            body: Body {
+                world: Grid::new(protocol.unknown()),
+                position: (0, 0),
+                heading: Heading::Up,
+                painted: HashSet::new(),
+            },
+            protocol,
+            steps: 0,
+        }
+    }
+
+    pub fn world(&self) -> &Grid {
+        &self.body.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut Grid {
+        &mut self.body.world
+    }
+
+    pub fn position(&self) -> Position {
+        self.body.position
+    }
+
+    pub fn heading(&self) -> Heading {
+        self.body.heading
+    }
+
+    // Every cell the robot has written to at least once.
+    pub fn painted(&self) -> &HashSet<Position> {
+        &self.body.painted
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    // Runs one sense/respond cycle, returning false once the program has halted.
+    pub fn step(&mut self) -> bool {
+        let body = &mut self.body;
+        let input = self.sensor.sense(&body.world, body.position, body.heading);
+        self.computer.set_input(input);
+        if !self.protocol.respond(&mut self.computer, input, body) {
+            return false;
+        }
+        self.steps += 1;
+        true
+    }
+
+    // Steps until the program halts, returning the number of steps taken.
+    pub fn run(&mut self) -> usize {
+        while self.step() {}
+        self.steps
+    }
+
+    // Draws the world with the robot on top of it.
+    pub fn render<F: Fn(Val) -> char>(&self, glyph: F) -> String {
+        self.body
+            .world
+            .render_with(glyph, &[(self.body.position, self.body.heading.glyph())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A program that reads an input before emitting each pair, then halts.
+    fn scripted(pairs: &[(Val, Val)]) -> Computer {
+        let mut program = vec![];
+        for &(value, turn) in pairs {
+            program.extend([3, 1000, 104, value, 104, turn]);
+        }
+        program.push(99);
+        Computer::new(program)
+    }
+
+    #[test]
+    fn test_heading() {
+        let heading = Heading::Up;
+        assert_eq!(heading.turn_left().turn_left(), Heading::Down);
+        assert_eq!(heading.turn_right().turn_left(), heading);
+        assert_eq!(Heading::Left.step((0, 0)), (-1, 0));
+        assert_eq!(Heading::Up.step((0, 0)), (0, -1));
+    }
+
+    #[test]
+    fn test_painting_example() {
+        let mut robot = Robot::new(scripted(&[
+            (1, 0),
+            (0, 0),
+            (1, 0),
+            (1, 0),
+            (0, 1),
+            (1, 0),
+            (1, 0),
+        ]));
+        assert_eq!(robot.run(), 7);
+        assert_eq!(robot.painted().len(), 6);
+        assert_eq!(robot.position(), (0, -1));
+        assert_eq!(robot.heading(), Heading::Left);
+        let glyph = |v| if v == 1 { '#' } else { '.' };
+        assert_eq!(robot.world().render(glyph), "..#\n..#\n##.\n");
+        assert_eq!(robot.render(glyph), ".<#\n..#\n##.\n");
+    }
+
+    #[test]
+    fn test_custom_sensor() {
+        // Echoes its reading back as the value to paint, always turning right.
+        let echo = Computer::new(vec![3, 100, 4, 100, 104, 1, 1105, 1, 0]);
+        let mut robot = Robot::with_sensor(echo, |world: &Grid, pos, heading| {
+            world.get(pos) + heading as Val + 10
+        });
+        robot.world_mut().set((0, 0), 5);
+        for _ in 0..4 {
+            assert!(robot.step());
+        }
+        assert_eq!(robot.position(), (0, 0));
+        assert_eq!(robot.world().get((0, 0)), 15);
+        assert_eq!(robot.world().get((1, 0)), 11);
+        assert_eq!(robot.world().get((1, 1)), 12);
+        assert_eq!(robot.world().get((0, 1)), 13);
+    }
+
+    #[test]
+    fn test_exploring() {
+        // Only ever lets the robot move east.
+        let corridor = Computer::new(vec![3, 100, 1008, 100, 4, 101, 4, 101, 1105, 1, 0]);
+        // Looks north wherever it hasn't yet, otherwise heads east.
+        let explorer = |world: &Grid, (x, y): Position, _heading| {
+            if world.get((x, y - 1)) == -1 {
+                MoveAndReport::command(Heading::Up)
+            } else {
+                MoveAndReport::command(Heading::Right)
+            }
+        };
+        let mut robot = Robot::with_protocol(corridor, explorer, MoveAndReport);
+        assert!(robot.step());
+        assert_eq!(robot.position(), (0, 0));
+        assert_eq!(robot.world().get((0, -1)), WALL);
+        assert!(robot.step());
+        assert_eq!(robot.position(), (1, 0));
+        assert_eq!(robot.world().get((1, 0)), MOVED);
+        for _ in 0..3 {
+            assert!(robot.step());
+        }
+        assert_eq!(robot.position(), (2, 0));
+        assert_eq!(robot.heading(), Heading::Up);
+        assert_eq!(robot.steps(), 5);
+        let glyph = |v| match v {
+            WALL => '#',
+            MOVED => '.',
+            _ => ' ',
+        };
+        assert_eq!(robot.render(glyph), "###\n .^\n");
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::memory::MAX_GROWTH;
+use crate::{Computer, Memory, Val};
+
+const MAGIC: &[u8; 4] = b"ICS1";
+
+// The complete state of a `Computer` in a compact binary form. Memory is written from its
+// contents alone (zeros dropped, and split into dense and sparse parts by where the nonzero
+// cells are rather than by how the machine's memory happened to grow), so two machines that
+// will behave identically have equal snapshots, which makes snapshots usable as keys when
+// searching over machine states.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Snapshot {
+    bytes: Vec<u8>,
+}
+
+impl Snapshot {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Snapshot> {
+        // Decode once up front so `Computer::restore` can't fail later.
+        decode(&bytes)?;
+        Ok(Snapshot { bytes })
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl Computer {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut out = MAGIC.to_vec();
+        let memory = &self.memory;
+        let mut cells: Vec<(usize, Val)> = memory
+            .dense()
+            .iter()
+            .copied()
+            .enumerate()
+            .chain(memory.sparse().iter().map(|(&addr, &value)| (addr, value)))
+            .filter(|&(_, value)| value != 0)
+            .collect();
+        cells.sort_unstable();
+        // The dense part covers the nonzero cells up to the first gap too big for `Memory`
+        // to fill in when growing; everything after that is sparse.
+        let mut used = 0;
+        let mut split = 0;
+        for &(addr, _) in &cells {
+            if addr >= used + MAX_GROWTH {
+                break;
+            }
+            used = addr + 1;
+            split += 1;
+        }
+        write_len(&mut out, used);
+        for addr in 0..used {
+            write_val(&mut out, memory.get(addr));
+        }
+        write_len(&mut out, cells.len() - split);
+        for &(addr, value) in &cells[split..] {
+            write_len(&mut out, addr);
+            write_val(&mut out, value);
+        }
+        write_len(&mut out, self.pos);
+        write_val(&mut out, self.relative_base);
+        write_len(&mut out, self.inputs.len());
+        for &value in &self.inputs {
+            write_val(&mut out, value);
+        }
+        write_len(&mut out, self.outputs.len());
+        for &value in &self.outputs {
+            write_val(&mut out, value);
+        }
+        out.push(self.finished as u8);
+        Snapshot { bytes: out }
+    }
+
+    pub fn restore(snapshot: &Snapshot) -> Computer {
+        decode(&snapshot.bytes).expect("snapshots are validated when created")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.snapshot().as_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Computer> {
+        decode(&fs::read(path)?)
+    }
+}
+
+fn bad_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Unsigned LEB128.
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_len(out: &mut Vec<u8>, n: usize) {
+    write_varint(out, n as u64);
+}
+
+// Zigzag-encoded so small negative values stay small.
+fn write_val(out: &mut Vec<u8>, value: Val) {
+    write_varint(out, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> io::Result<u8> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| bad_data("truncated snapshot"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut n: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(bad_data("varint too long"))
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        usize::try_from(self.varint()?).map_err(|_| bad_data("length out of range"))
+    }
+
+    fn val(&mut self) -> io::Result<Val> {
+        let n = self.varint()?;
+        Ok((n >> 1) as Val ^ -((n & 1) as Val))
+    }
+
+    fn vals(&mut self) -> io::Result<Vec<Val>> {
+        let count = self.len()?;
+        (0..count).map(|_| self.val()).collect()
+    }
+}
+
+fn decode(bytes: &[u8]) -> io::Result<Computer> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(bad_data("not an intcode snapshot"));
+    }
+    let mut reader = Reader {
+        bytes,
+        pos: MAGIC.len(),
+    };
+    let dense = reader.vals()?;
+    let mut sparse = HashMap::new();
+    for _ in 0..reader.len()? {
+        let addr = reader.len()?;
+        // `Memory::get` would never look there.
+        if addr < dense.len() {
+            return Err(bad_data("sparse address inside dense memory"));
+        }
+        sparse.insert(addr, reader.val()?);
+    }
+    let mut computer = Computer::new(vec![]);
+    computer.memory = Memory::from_parts(dense, sparse);
+    computer.pos = reader.len()?;
+    computer.relative_base = reader.val()?;
+    computer.inputs = reader.vals()?.into();
+    computer.outputs = reader.vals()?;
+    computer.finished = match reader.byte()? {
+        0 => false,
+        1 => true,
+        _ => return Err(bad_data("bad finished flag")),
+    };
+    if reader.pos != bytes.len() {
+        return Err(bad_data("trailing bytes in snapshot"));
+    }
+    Ok(computer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RunState;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Doubles each input forever.
+    fn doubler() -> Computer {
+        Computer::new(vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut comp = doubler();
+        comp.set_input(-3);
+        comp.set_input(4);
+        comp.run_until_output();
+        comp.memory.set(1_000_000, -42);
+
+        let snapshot = comp.snapshot();
+        let mut restored = Computer::restore(&snapshot);
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.all_outputs(), &vec![-6]);
+        assert_eq!(restored.memory.get(1_000_000), -42);
+        assert_eq!(restored.run_until_output(), RunState::Output(8));
+        assert_eq!(comp.run_until_output(), RunState::Output(8));
+    }
+
+    #[test]
+    fn test_equal_states() {
+        let mut a = doubler();
+        let mut b = doubler();
+        // Growing memory with zeros doesn't change behaviour.
+        b.memory.set(100, 0);
+        assert_eq!(a.snapshot(), b.snapshot());
+
+        a.set_input(1);
+        a.run_until_output();
+        let seen: HashSet<Snapshot> = [a.snapshot(), b.snapshot()].into_iter().collect();
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn test_write_order() {
+        // The same contents, but only `b` grows its dense memory far enough for the write to
+        // 5000 to land there.
+        let mut a = Computer::new(vec![1, 2, 3, 4]);
+        let mut b = a.clone();
+        a.memory.set(5000, 7);
+        b.memory.set(4000, 0);
+        b.memory.set(5000, 7);
+        assert_ne!(a.memory.dense_len(), b.memory.dense_len());
+        assert_eq!(a.snapshot(), b.snapshot());
+        let restored = Computer::restore(&b.snapshot());
+        assert_eq!(restored.memory.get(5000), 7);
+        assert_eq!(restored.snapshot(), a.snapshot());
+    }
+
+    // A file in the temp directory that no other test run will use, removed when dropped
+    // (even if the test fails).
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new() -> TempFile {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let name = format!(
+                "intcode-snapshot-test-{}-{}.bin",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            );
+            TempFile(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let file = TempFile::new();
+        let mut comp = doubler();
+        comp.set_input(21);
+        comp.run_until_output();
+        comp.save(&file.0).unwrap();
+        let loaded = Computer::load(&file.0).unwrap();
+        assert_eq!(loaded.snapshot(), comp.snapshot());
+        assert_eq!(loaded.final_output(), Some(&42));
+    }
+
+    #[test]
+    fn test_bad_data() {
+        assert!(Snapshot::from_bytes(b"nope".to_vec()).is_err());
+        let mut bytes = doubler().snapshot().as_bytes().to_vec();
+        bytes.pop();
+        assert!(Snapshot::from_bytes(bytes).is_err());
+        // A sparse cell hidden under the dense part.
+        let mut bytes = MAGIC.to_vec();
+        for n in [2, 0, 0, 1, 1, 10, 0, 0, 0, 0, 0] {
+            write_len(&mut bytes, n);
+        }
+        assert!(Snapshot::from_bytes(bytes.clone()).is_err());
+        bytes[MAGIC.len() + 4] = 2;
+        assert!(Snapshot::from_bytes(bytes).is_ok());
+    }
+}
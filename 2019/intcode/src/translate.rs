@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use crate::{to_address, Computer, ControlFlowGraph, Instruction, Operand, RunState, Val};
+
+// An operand with its parameter mode already resolved.
+#[derive(Clone, Copy, Debug)]
+enum Arg {
+    Imm(Val),
+    Pos(usize),
+    Rel(Val),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Code {
+    Add(Arg, Arg, Arg),
+    Mul(Arg, Arg, Arg),
+    Input(Arg),
+    Output(Arg),
+    JumpT(Arg, Arg),
+    JumpF(Arg, Arg),
+    LT(Arg, Arg, Arg),
+    EQ(Arg, Arg, Arg),
+    RbOffset(Arg),
+    End,
+}
+
+// A program pre-decoded into bytecode, built from the instructions reachable in its
+// control-flow graph. Anything it doesn't cover (e.g. code only reached through computed
+// jumps) is left to the interpreter.
+#[derive(Debug)]
+pub struct Translation {
+    code: Vec<Option<Code>>,
+    // Addresses whose values were baked into `code`.
+    covered: Vec<bool>,
+}
+
+impl Translation {
+    pub fn new(program: &[Val]) -> Translation {
+        use Instruction::*;
+        let mut code = vec![None; program.len()];
+        let mut covered = vec![false; program.len()];
+        let cfg = ControlFlowGraph::build(program);
+        for op in cfg.blocks.values().flat_map(|block| &block.ops) {
+            let args: Option<Vec<Arg>> = op
+                .operands
+                .iter()
+                .map(|operand| match *operand {
+                    // Negative addresses are left for the interpreter to reject.
+                    Operand::Position(addr) => usize::try_from(addr).ok().map(Arg::Pos),
+                    Operand::Immediate(value) => Some(Arg::Imm(value)),
+                    Operand::Relative(offset) => Some(Arg::Rel(offset)),
+                })
+                .collect();
+            let a = match args {
+                Some(args) => args,
+                None => continue,
+            };
+            code[op.addr] = Some(match op.instruction {
+                Add => Code::Add(a[0], a[1], a[2]),
+                Mul => Code::Mul(a[0], a[1], a[2]),
+                Input => Code::Input(a[0]),
+                Output => Code::Output(a[0]),
+                JumpT => Code::JumpT(a[0], a[1]),
+                JumpF => Code::JumpF(a[0], a[1]),
+                LT => Code::LT(a[0], a[1], a[2]),
+                EQ => Code::EQ(a[0], a[1], a[2]),
+                RbOffset => Code::RbOffset(a[0]),
+                End => Code::End,
+            });
+            covered[op.addr..op.next()].fill(true);
+        }
+        Translation { code, covered }
+    }
+
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.covered.get(addr).copied().unwrap_or(false)
+    }
+
+    pub fn num_instructions(&self) -> usize {
+        self.code.iter().filter(|code| code.is_some()).count()
+    }
+}
+
+impl Computer {
+    // Pre-decodes the program so that repeated runs skip instruction decoding. Clones share
+    // the translation, so translate once before cloning. A write into translated code drops
+    // the translation and execution carries on in the interpreter.
+    pub fn enable_translation(&mut self) {
+        self.translation = Some(Arc::new(Translation::new(self.memory.dense())));
+    }
+
+    pub fn is_translated(&self) -> bool {
+        self.translation.is_some()
+    }
+
+    #[inline]
+    fn arg(&self, arg: Arg) -> Val {
+        match arg {
+            Arg::Imm(value) => value,
+            Arg::Pos(addr) => self.memory.get(addr),
+            Arg::Rel(offset) => self.memory.get(to_address(self.relative_base + offset)),
+        }
+    }
+
+    #[inline]
+    fn arg_address(&self, arg: Arg) -> usize {
+        match arg {
+            Arg::Pos(addr) => addr,
+            Arg::Rel(offset) => to_address(self.relative_base + offset),
+            Arg::Imm(_) => unreachable!("immediate write targets are never translated"),
+        }
+    }
+
+    pub(crate) fn run_translated(&mut self) -> RunState {
+        let translation = Arc::clone(self.translation.as_ref().unwrap());
+        while self.translation.is_some() {
+            let code = match translation.code.get(self.pos) {
+                Some(Some(code)) => *code,
+                _ => match self.step() {
+                    Some(state) => return state,
+                    None => continue,
+                },
+            };
+            match code {
+                Code::Add(a, b, c) => {
+                    let value = self.arg(a) + self.arg(b);
+                    self.write(self.arg_address(c), value);
+                    self.pos += 4;
+                }
+                Code::Mul(a, b, c) => {
+                    let value = self.arg(a) * self.arg(b);
+                    self.write(self.arg_address(c), value);
+                    self.pos += 4;
+                }
+                Code::Input(a) => match self.inputs.pop_front() {
+                    Some(input) => {
+                        self.write(self.arg_address(a), input);
+                        self.pos += 2;
+                    }
+                    None => return RunState::NeedsInput,
+                },
+                Code::Output(a) => {
+                    let out = self.arg(a);
+                    self.pos += 2;
+                    self.outputs.push(out);
+                    return RunState::Output(out);
+                }
+                Code::JumpT(a, b) => {
+                    if self.arg(a) != 0 {
+                        self.pos = to_address(self.arg(b));
+                    } else {
+                        self.pos += 3;
+                    }
+                }
+                Code::JumpF(a, b) => {
+                    if self.arg(a) == 0 {
+                        self.pos = to_address(self.arg(b));
+                    } else {
+                        self.pos += 3;
+                    }
+                }
+                Code::LT(a, b, c) => {
+                    let value = (self.arg(a) < self.arg(b)) as Val;
+                    self.write(self.arg_address(c), value);
+                    self.pos += 4;
+                }
+                Code::EQ(a, b, c) => {
+                    let value = (self.arg(a) == self.arg(b)) as Val;
+                    self.write(self.arg_address(c), value);
+                    self.pos += 4;
+                }
+                Code::RbOffset(a) => {
+                    self.relative_base += self.arg(a);
+                    self.pos += 2;
+                }
+                Code::End => {
+                    self.finished = true;
+                    return RunState::Halted;
+                }
+            }
+        }
+        // The program rewrote its own code, so carry on without the translation.
+        self.run_until_output()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::InputReader;
+
+    // Runs the program with and without translation and checks they end up identical.
+    fn assert_same(comp: &Computer, inputs: &[Val]) -> Computer {
+        let mut interpreted = comp.clone();
+        let mut translated = comp.clone();
+        translated.enable_translation();
+        for &input in inputs {
+            interpreted.set_input(input);
+            translated.set_input(input);
+        }
+        let interpreted_result = interpreted.execute();
+        let translated_result = translated.execute();
+        assert_eq!(interpreted_result, translated_result);
+        assert_eq!(interpreted.snapshot(), translated.snapshot());
+        translated
+    }
+
+    fn from_input(path: &'static str) -> Computer {
+        Computer::from_reader(&InputReader::new(path))
+    }
+
+    #[test]
+    fn test_examples() {
+        // Day 2 programs overwrite their own operands.
+        let comp = assert_same(
+            &Computer::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]),
+            &[],
+        );
+        assert!(!comp.is_translated());
+        let compare_to_8 = Computer::new(vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ]);
+        for input in 6..11 {
+            // Only data is written, so the translation survives.
+            assert!(assert_same(&compare_to_8, &[input]).is_translated());
+        }
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let comp = assert_same(&Computer::new(quine.clone()), &[]);
+        assert_eq!(comp.all_outputs(), &quine);
+        assert_same(
+            &Computer::new(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]),
+            &[],
+        );
+        assert_same(&Computer::new(vec![104, 1125899906842624, 99]), &[]);
+    }
+
+    #[test]
+    fn test_self_modification_falls_back() {
+        // The 33 never decodes, so it isn't translated and rewriting it is fine.
+        let comp = assert_same(&Computer::new(vec![1002, 4, 3, 4, 33]), &[]);
+        assert!(comp.is_translated());
+        let comp = assert_same(&Computer::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]), &[]);
+        assert!(!comp.is_translated());
+        assert_eq!(comp.snapshot(), {
+            let mut interpreted = Computer::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]);
+            interpreted.execute();
+            interpreted.snapshot()
+        });
+    }
+
+    #[test]
+    fn test_puzzle_inputs() {
+        let mut day02 = from_input("../day-02/input.txt");
+        day02.set_noun_verb(12, 2);
+        assert_eq!(assert_same(&day02, &[]).execute(), 3409710);
+
+        let day05 = from_input("../day-05/input.txt");
+        assert_eq!(assert_same(&day05, &[1]).final_output(), Some(&13087969));
+        assert_eq!(assert_same(&day05, &[5]).final_output(), Some(&14110739));
+
+        let day07 = from_input("../day-07/input.txt");
+        for phase in 0..10 {
+            assert_same(&day07, &[phase, 17]);
+        }
+
+        let day09 = from_input("../day-09/input.txt");
+        assert_eq!(assert_same(&day09, &[1]).final_output(), Some(&2399197539));
+        assert_eq!(assert_same(&day09, &[2]).final_output(), Some(&35106));
+    }
+}
@@ -1,141 +1,148 @@
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::hash::{Hash, Hasher};
+use std::collections::HashMap;
+use std::env;
 
 use common::{default_puzzle, Puzzle};
 
 const START: &str = "start";
 const END: &str = "end";
 
-#[derive(Clone)]
-struct Path {
-    rooms: Vec<String>,
-
-    // The special small room that gets two visits.
-    special_room: Option<String>,
-}
-
-impl PartialEq for Path {
-    fn eq(&self, other: &Self) -> bool {
-        self.rooms == other.rooms
-    }
-}
-
-impl Eq for Path {}
-
-impl Hash for Path {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.rooms.hash(state);
-    }
+struct CaveSystem {
+    // Caves are numbered in the order they first appear.
+    names: Vec<String>,
+    small: Vec<bool>,
+    connections: Vec<Vec<usize>>,
+    start: usize,
+    end: usize,
 }
 
-impl Path {
-    fn new(from: String, to: String, special_room: Option<String>) -> Self {
-        let rooms = vec![from, to];
-        Path {
-            rooms,
-            special_room,
+impl CaveSystem {
+    fn new(lines: &[String]) -> Self {
+        let mut names: Vec<String> = vec![];
+        let mut connections: Vec<Vec<usize>> = vec![];
+        let mut intern = |name: &str, connections: &mut Vec<Vec<usize>>| {
+            names.iter().position(|n| n == name).unwrap_or_else(|| {
+                names.push(name.to_string());
+                connections.push(vec![]);
+                names.len() - 1
+            })
+        };
+        for line in lines.iter() {
+            let parts: Vec<&str> = line.split('-').collect();
+            assert_eq!(parts.len(), 2);
+            let from = intern(parts[0], &mut connections);
+            let to = intern(parts[1], &mut connections);
+            connections[from].push(to);
+            connections[to].push(from);
+        }
+        // the visited caves are kept in a bitmask
+        assert!(names.len() <= 64, "Too many caves.");
+        let small = names
+            .iter()
+            .map(|n| n.chars().next().unwrap().is_lowercase())
+            .collect();
+        let find = |name| names.iter().position(|n| n == name).expect("Missing cave.");
+        let (start, end) = (find(START), find(END));
+        CaveSystem {
+            names,
+            small,
+            connections,
+            start,
+            end,
         }
     }
 
-    fn may_visit_room(&mut self, room: &str) -> bool {
-        if room.chars().next().unwrap().is_uppercase() {
-            return true;
-        }
-        let visited = self.rooms.iter().any(|r| r == room);
-        if !visited {
-            return true;
-        }
-        let mut allowed = false;
-        if let Some(r) = &self.special_room {
-            if r == room {
-                allowed = true;
-                self.special_room = None;
-            }
-        }
-        allowed
+    fn get_small_rooms(&self) -> Vec<String> {
+        (0..self.names.len())
+            .filter(|&cave| self.small[cave] && cave != self.start && cave != self.end)
+            .map(|cave| self.names[cave].clone())
+            .collect()
     }
 
-    fn maybe_extend(&mut self, room: &str) -> bool {
-        let mut extended = true;
-        if self.may_visit_room(room) {
-            self.rooms.push(room.to_string());
+    // The bit for `cave` in a visited mask, which only tracks small caves.
+    fn bit(&self, cave: usize) -> u64 {
+        if self.small[cave] {
+            1 << cave
         } else {
-            extended = false;
+            0
         }
-        extended
     }
-}
-
-struct CaveSystem {
-    connections: HashMap<String, Vec<String>>,
-}
 
-impl CaveSystem {
-    fn new(lines: &[String]) -> Self {
-        let segments: Vec<(String, String)> = lines
-            .iter()
-            .map(|l| {
-                let parts: Vec<&str> = l.split('-').collect();
-                assert_eq!(parts.len(), 2);
-                (parts[0].to_string(), parts[1].to_string())
-            })
-            .collect();
-        let mut connections: HashMap<String, Vec<String>> = HashMap::new();
-        for (from, to) in segments.iter() {
-            if let Some(v) = connections.get_mut(from) {
-                v.push(to.to_string());
+    // The caves a path at `cave` can go to next, with the visited mask and whether a small
+    // cave may still be visited twice after going there.
+    fn next_steps(
+        &self,
+        cave: usize,
+        visited: u64,
+        revisit: bool,
+    ) -> impl Iterator<Item = (usize, u64, bool)> + '_ {
+        self.connections[cave].iter().filter_map(move |&next| {
+            if next == self.start {
+                None
+            } else if visited & self.bit(next) == 0 {
+                Some((next, visited | self.bit(next), revisit))
+            } else if revisit {
+                Some((next, visited, false))
             } else {
-                connections.insert(from.to_string(), vec![to.to_string()]);
+                None
             }
-            if let Some(v) = connections.get_mut(to) {
-                v.push(from.to_string());
-            } else {
-                connections.insert(to.to_string(), vec![from.to_string()]);
-            }
-        }
-        CaveSystem { connections }
+        })
     }
 
-    fn get_small_rooms(&self) -> Vec<String> {
-        let mut rooms = vec![];
-        for room in self.connections.keys() {
-            if room.as_str() == START || room.as_str() == END {
-                continue;
-            }
-            if room.chars().next().unwrap().is_lowercase() {
-                rooms.push(room.to_string());
-            }
+    // Counts the paths from `cave` to the end. The count only depends on where the path is,
+    // which small caves it has been through and whether it can still revisit one, so it's
+    // memoized on those. (Two big caves next to each other would allow endless paths.)
+    fn count_from(
+        &self,
+        cave: usize,
+        visited: u64,
+        revisit: bool,
+        memo: &mut HashMap<(usize, u64, bool), u64>,
+    ) -> u64 {
+        if cave == self.end {
+            return 1;
+        }
+        if let Some(&count) = memo.get(&(cave, visited, revisit)) {
+            return count;
         }
-        rooms
+        let count = self
+            .next_steps(cave, visited, revisit)
+            .map(|(next, visited, revisit)| self.count_from(next, visited, revisit, memo))
+            .sum();
+        memo.insert((cave, visited, revisit), count);
+        count
     }
 
+    // With `special_small_room`, one small cave (other than the start and end) may be
+    // visited twice.
     fn count_paths(&self, special_small_room: bool) -> u64 {
-        let mut paths: HashSet<Path> = HashSet::new();
-        let mut queue = VecDeque::new();
-        for dest in self.connections[START].iter() {
-            if special_small_room {
-                for room in self.get_small_rooms().into_iter() {
-                    queue.push_back(Path::new(START.to_string(), dest.to_string(), Some(room)));
-                }
-            } else {
-                queue.push_back(Path::new(START.to_string(), dest.to_string(), None));
+        let mut memo = HashMap::new();
+        self.count_from(
+            self.start,
+            self.bit(self.start),
+            special_small_room,
+            &mut memo,
+        )
+    }
+
+    // Lists every path, as comma separated cave names. Only practical for small examples.
+    fn list_paths(&self, special_small_room: bool) -> Vec<String> {
+        let mut paths = vec![];
+        let mut stack = vec![(vec![self.start], self.bit(self.start), special_small_room)];
+        while let Some((path, visited, revisit)) = stack.pop() {
+            let cave = *path.last().unwrap();
+            if cave == self.end {
+                let names: Vec<&str> = path.iter().map(|&c| self.names[c].as_str()).collect();
+                paths.push(names.join(","));
+                continue;
             }
-        }
-        while !queue.is_empty() {
-            let path = queue.pop_front().unwrap();
-            let last = path.rooms.iter().last().unwrap();
-            for dest in self.connections[last].iter() {
-                let mut new_path = path.clone();
-                if new_path.maybe_extend(dest) {
-                    if dest == END {
-                        paths.insert(new_path);
-                    } else {
-                        queue.push_back(new_path);
-                    }
-                }
+            for (next, visited, revisit) in self.next_steps(cave, visited, revisit) {
+                let mut path = path.clone();
+                path.push(next);
+                stack.push((path, visited, revisit));
             }
         }
-        paths.len() as u64
+        paths.sort();
+        paths
     }
 }
 
@@ -156,15 +163,26 @@ fn get_puzzle() -> Puzzle {
 
 fn main() {
     get_puzzle().run();
+    // lists the part 1 paths through the input's caves
+    if env::args().any(|arg| arg == "--list") {
+        let lines: Vec<String> = get_puzzle().get_reader().parsed_lines();
+        let cave = CaveSystem::new(&lines);
+        println!("Small caves: {}", cave.get_small_rooms().join(", "));
+        for path in cave.list_paths(false) {
+            println!("{}", path);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use super::*;
 
     #[test]
     fn test_example1() {
-        let lines: Vec<String> = vec!["start-A", "start-b", "A-c", "A-b", "b-d", "A-end", "b-end"]
+        let lines: Vec<String> = ["start-A", "start-b", "A-c", "A-b", "b-d", "A-end", "b-end"]
             .iter()
             .map(|l| l.to_string())
             .collect();
@@ -175,11 +193,19 @@ mod tests {
         );
         assert_eq!(cave.count_paths(false), 10);
         assert_eq!(cave.count_paths(true), 36);
+        let paths = cave.list_paths(false);
+        assert_eq!(paths.len(), 10);
+        assert!(paths.contains(&"start,A,b,A,c,A,end".to_string()));
+        assert!(paths.contains(&"start,b,end".to_string()));
+        let paths = cave.list_paths(true);
+        assert_eq!(paths.len(), 36);
+        assert!(paths.contains(&"start,A,b,d,b,A,c,A,end".to_string()));
+        assert!(!paths.contains(&"start,A,b,A,b,A,c,A,c,A,end".to_string()));
     }
 
     #[test]
     fn test_example2() {
-        let lines: Vec<String> = vec![
+        let lines: Vec<String> = [
             "dc-end", "HN-start", "start-kj", "dc-start", "dc-HN", "LN-dc", "HN-end", "kj-sa",
             "kj-HN", "kj-dc",
         ]
@@ -193,11 +219,12 @@ mod tests {
         );
         assert_eq!(cave.count_paths(false), 19);
         assert_eq!(cave.count_paths(true), 103);
+        assert_eq!(cave.list_paths(true).len(), 103);
     }
 
     #[test]
     fn test_example3() {
-        let lines: Vec<String> = vec![
+        let lines: Vec<String> = [
             "fs-end", "he-DX", "fs-he", "start-DX", "pj-DX", "end-zg", "zg-sl", "zg-pj", "pj-he",
             "RW-he", "fs-DX", "pj-RW", "zg-RW", "start-pj", "he-WI", "zg-he", "pj-fs", "start-RW",
         ]
@@ -211,6 +238,7 @@ mod tests {
         );
         assert_eq!(cave.count_paths(false), 226);
         assert_eq!(cave.count_paths(true), 3509);
+        assert_eq!(cave.list_paths(true).len(), 3509);
     }
 
     #[test]
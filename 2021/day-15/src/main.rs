@@ -1,10 +1,13 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::env;
 
 use common::Puzzle;
 
-// type Point = (usize, usize);
+const PATH_COLOUR: &str = "\x1b[1;33m";
+const RESET_COLOUR: &str = "\x1b[0m";
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 struct Point {
     x: usize,
     y: usize,
@@ -75,36 +78,73 @@ impl Cave {
             .collect()
     }
 
-    fn best_path_cost(&self) -> u64 {
+    fn index(&self, p: Point) -> usize {
+        p.y * self.width + p.x
+    }
+
+    // A lower bound on the risk from `p` to the bottom right, since every step costs at least 1.
+    fn min_risk_to_end(&self, p: Point) -> u64 {
+        (self.width - 1 - p.x + self.height - 1 - p.y) as u64
+    }
+
+    // The path with the lowest total risk from the top left to the bottom right, found with
+    // A*, along with its risk (which doesn't count the starting point).
+    fn best_path(&self) -> (u64, Vec<Point>) {
         let start_point = Point::from((0, 0));
         let end_point = Point::from((self.width - 1, self.height - 1));
-        let mut best_paths: HashMap<Point, (Point, u64)> = HashMap::new();
-        best_paths.insert(start_point, (start_point, 0));
-        let mut visited = vec![vec![false; self.height]; self.width];
-
-        let mut current_point = start_point;
-        while current_point != end_point {
-            visited[current_point.y][current_point.x] = true;
-            let destinations = self.get_neighbors(current_point);
-            let cost_so_far = best_paths.get(&current_point).unwrap().1;
-            for next_point in destinations.into_iter() {
+        let mut best_costs = vec![u64::MAX; self.width * self.height];
+        let mut came_from = vec![start_point; self.width * self.height];
+        best_costs[self.index(start_point)] = 0;
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((self.min_risk_to_end(start_point), 0, start_point)));
+        while let Some(Reverse((_, cost_so_far, current_point))) = queue.pop() {
+            if current_point == end_point {
+                break;
+            }
+            if cost_so_far > best_costs[self.index(current_point)] {
+                continue;
+            }
+            for next_point in self.get_neighbors(current_point) {
                 let next_cost = cost_so_far + self.grid[next_point.y][next_point.x] as u64;
-                if let Some(current_best) = best_paths.get_mut(&next_point) {
-                    if next_cost < current_best.1 {
-                        *current_best = (current_point, next_cost);
-                    }
+                let next_idx = self.index(next_point);
+                if next_cost < best_costs[next_idx] {
+                    best_costs[next_idx] = next_cost;
+                    came_from[next_idx] = current_point;
+                    let estimate = next_cost + self.min_risk_to_end(next_point);
+                    queue.push(Reverse((estimate, next_cost, next_point)));
+                }
+            }
+        }
+        let mut path = vec![end_point];
+        while *path.last().unwrap() != start_point {
+            path.push(came_from[self.index(*path.last().unwrap())]);
+        }
+        path.reverse();
+        (best_costs[self.index(end_point)], path)
+    }
+
+    fn best_path_cost(&self) -> u64 {
+        self.best_path().0
+    }
+
+    // Draws the risk levels, highlighting the ones on `path` for the terminal.
+    fn render(&self, path: &[Point]) -> String {
+        let mut on_path = vec![false; self.width * self.height];
+        for &p in path {
+            on_path[self.index(p)] = true;
+        }
+        let mut out = String::new();
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, risk) in row.iter().enumerate() {
+                if on_path[self.index(Point::from((x, y)))] {
+                    out.push_str(&format!("{}{}{}", PATH_COLOUR, risk, RESET_COLOUR));
                 } else {
-                    best_paths.insert(next_point, (current_point, next_cost));
+                    out.push_str(&risk.to_string());
                 }
             }
-            current_point = *best_paths
-                .iter()
-                .filter(|(p, _)| !visited[p.y][p.x])
-                .min_by(|(_, a), (_, b)| a.1.cmp(&b.1))
-                .unwrap()
-                .0;
+            out.push('\n');
         }
-        best_paths.get(&end_point).unwrap().1
+        out
     }
 }
 
@@ -127,6 +167,12 @@ fn get_puzzle(filename: &'static str) -> Puzzle {
 fn main() {
     get_puzzle("input.txt").run();
     get_puzzle("input2.txt").run();
+    // draws the safest way through the first cave
+    if env::args().any(|arg| arg == "--show") {
+        let cave = Cave::new(get_puzzle("input.txt").get_reader().parsed_lines());
+        let (_, path) = cave.best_path();
+        print!("{}", cave.render(&path));
+    }
 }
 
 #[cfg(test)]
@@ -135,7 +181,7 @@ mod tests {
 
     #[test]
     fn test_example() {
-        let lines = vec![
+        let lines = [
             "1163751742",
             "1381373672",
             "2136511328",
@@ -156,6 +202,37 @@ mod tests {
         assert_eq!(cave.best_path_cost(), 315);
     }
 
+    #[test]
+    fn test_path() {
+        let lines = ["1163", "1381", "2136"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        let mut cave = Cave::new(lines);
+        let (cost, path) = cave.best_path();
+        // down, down, then along the bottom
+        assert_eq!(cost, 1 + 2 + 1 + 3 + 6);
+        assert_eq!(path.len(), 6);
+        assert_eq!(path[0], Point::from((0, 0)));
+        assert_eq!(path[2], Point::from((0, 2)));
+        assert_eq!(path[5], Point::from((3, 2)));
+        let highlight = |risk| format!("{}{}{}", PATH_COLOUR, risk, RESET_COLOUR);
+        assert_eq!(
+            cave.render(&path[..3]),
+            format!(
+                "{}163\n{}381\n{}136\n",
+                highlight(1),
+                highlight(1),
+                highlight(2)
+            )
+        );
+        // the bigger cave keeps its shape
+        cave.expand5x();
+        assert_eq!((cave.width, cave.height), (20, 15));
+        assert_eq!(cave.grid[14][19], 5);
+        assert_eq!(cave.best_path().1.len(), 20 + 15 - 1);
+    }
+
     #[test]
     fn test_part1() {
         get_puzzle("input.txt").test_part1(361);
@@ -167,13 +244,11 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // several minutes
     fn test_part2() {
         get_puzzle("input.txt").test_part2(2838);
     }
 
     #[test]
-    #[ignore] // several minutes
     fn test_another_part2() {
         get_puzzle("input2.txt").test_part2(3045);
     }
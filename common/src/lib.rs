@@ -1,5 +1,6 @@
 mod input;
 mod puzzle;
+pub mod vm;
 
 pub use crate::input::{BadInput, InputReader};
 pub use crate::puzzle::{Answer, Puzzle};
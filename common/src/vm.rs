@@ -0,0 +1,312 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+
+// The value held by a register.
+pub type Word = i64;
+
+// A fixed set of registers named by single characters, stored in an array in name order.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Registers {
+    names: Vec<char>,
+    values: Vec<Word>,
+}
+
+impl Registers {
+    // One register per character of `names`, all starting at zero.
+    pub fn new(names: &str) -> Registers {
+        let names: Vec<char> = names.chars().collect();
+        Registers {
+            values: vec![0; names.len()],
+            names,
+        }
+    }
+
+    pub fn slot(&self, register: char) -> usize {
+        self.names
+            .iter()
+            .position(|&name| name == register)
+            .unwrap_or_else(|| panic!("Unknown register: {}", register))
+    }
+
+    pub fn get(&self, register: char) -> Word {
+        self.values[self.slot(register)]
+    }
+
+    pub fn set(&mut self, register: char, value: Word) {
+        let slot = self.slot(register);
+        self.values[slot] = value;
+    }
+
+    pub fn names(&self) -> &[char] {
+        &self.names
+    }
+
+    pub fn values(&self) -> &[Word] {
+        &self.values
+    }
+}
+
+// What a machine should do after running an instruction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Flow {
+    Next,
+    // Moves the program counter by this much (relative to the instruction that jumped).
+    Jump(Word),
+    // Emits a value, then carries on with the next instruction.
+    Output(Word),
+    Halt,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Step {
+    Ran,
+    Output(Word),
+    Halted,
+    // The machine has used up its step limit.
+    OutOfSteps,
+}
+
+// An instruction for a `Machine`. A puzzle's toy assembly language only needs to say what
+// each instruction does; the machine takes care of the program counter and bookkeeping.
+pub trait InstructionSet: Clone + Debug + Eq + Hash {
+    fn execute(&self, machine: &mut Machine<Self>) -> Flow;
+}
+
+// Everything that decides what a machine does next, including its program, which
+// instructions may rewrite. Seeing the same state twice means the machine is in a loop.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct State<I> {
+    registers: Vec<Word>,
+    pos: Word,
+    program: Vec<I>,
+}
+
+// A register machine running a program until its program counter leaves the program.
+#[derive(Clone, Debug)]
+pub struct Machine<I> {
+    registers: Registers,
+    program: Vec<I>,
+    pos: Word,
+    steps: u64,
+    step_limit: Option<u64>,
+}
+
+impl<I: InstructionSet> Machine<I> {
+    pub fn new(program: Vec<I>, registers: &str) -> Machine<I> {
+        Machine {
+            registers: Registers::new(registers),
+            program,
+            pos: 0,
+            steps: 0,
+            step_limit: None,
+        }
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn get(&self, register: char) -> Word {
+        self.registers.get(register)
+    }
+
+    pub fn set(&mut self, register: char, value: Word) {
+        self.registers.set(register, value);
+    }
+
+    pub fn program(&self) -> &[I] {
+        &self.program
+    }
+
+    // For self-modifying instruction sets.
+    pub fn program_mut(&mut self) -> &mut [I] {
+        &mut self.program
+    }
+
+    pub fn pos(&self) -> Word {
+        self.pos
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Stops `step` and `run` once this many instructions have run in total.
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.step_limit = limit;
+    }
+
+    // How many more instructions can run before the step limit, if there is one.
+    pub fn remaining_steps(&self) -> Option<u64> {
+        self.step_limit
+            .map(|limit| limit.saturating_sub(self.steps))
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.current().is_none()
+    }
+
+    pub fn current(&self) -> Option<&I> {
+        usize::try_from(self.pos)
+            .ok()
+            .and_then(|pos| self.program.get(pos))
+    }
+
+    // Moves the program counter by `offset` and counts `steps` instructions as run, for
+    // optimizers that replace a run of instructions with its overall effect.
+    pub fn advance(&mut self, offset: Word, steps: u64) {
+        self.pos += offset;
+        self.steps += steps;
+    }
+
+    // Whether the machine is in `state`, without taking a copy of its own.
+    pub fn is_in_state(&self, state: &State<I>) -> bool {
+        self.pos == state.pos
+            && self.registers.values == state.registers
+            && self.program == state.program
+    }
+
+    pub fn state(&self) -> State<I> {
+        State {
+            registers: self.registers.values.clone(),
+            pos: self.pos,
+            program: self.program.clone(),
+        }
+    }
+
+    pub fn step(&mut self) -> Step {
+        if self.step_limit.is_some_and(|limit| self.steps >= limit) {
+            return Step::OutOfSteps;
+        }
+        let instruction = match self.current() {
+            Some(instruction) => instruction.clone(),
+            None => return Step::Halted,
+        };
+        let mut result = Step::Ran;
+        match instruction.execute(self) {
+            Flow::Next => self.pos += 1,
+            Flow::Jump(offset) => self.pos += offset,
+            Flow::Output(value) => {
+                self.pos += 1;
+                result = Step::Output(value);
+            }
+            Flow::Halt => {
+                self.pos = self.program.len() as Word;
+                result = Step::Halted;
+            }
+        }
+        self.steps += 1;
+        result
+    }
+
+    // Runs until the machine halts or runs out of steps, ignoring any output.
+    pub fn run(&mut self) -> Step {
+        self.run_traced(|_| {})
+    }
+
+    // Like `run`, but calls `trace` before every instruction.
+    pub fn run_traced<F: FnMut(&Machine<I>)>(&mut self, mut trace: F) -> Step {
+        loop {
+            trace(self);
+            match self.step() {
+                Step::Ran | Step::Output(_) => {}
+                done => return done,
+            }
+        }
+    }
+
+    // Runs until the next output, returning None if the machine halts or runs out of
+    // steps first.
+    pub fn run_until_output(&mut self) -> Option<Word> {
+        loop {
+            match self.step() {
+                Step::Ran => {}
+                Step::Output(value) => return Some(value),
+                Step::Halted | Step::OutOfSteps => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts `n` down to zero, outputting each value, then halts explicitly.
+    #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+    enum Countdown {
+        Out,
+        Dec,
+        Jnz(Word),
+        Stop,
+    }
+
+    impl InstructionSet for Countdown {
+        fn execute(&self, machine: &mut Machine<Self>) -> Flow {
+            match self {
+                Countdown::Out => Flow::Output(machine.get('n')),
+                Countdown::Dec => {
+                    machine.set('n', machine.get('n') - 1);
+                    Flow::Next
+                }
+                Countdown::Jnz(offset) if machine.get('n') != 0 => Flow::Jump(*offset),
+                Countdown::Jnz(_) => Flow::Next,
+                Countdown::Stop => Flow::Halt,
+            }
+        }
+    }
+
+    fn countdown(n: Word) -> Machine<Countdown> {
+        use Countdown::*;
+        let mut machine = Machine::new(vec![Out, Dec, Jnz(-2), Stop, Out], "mn");
+        machine.set('n', n);
+        machine
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut registers = Registers::new("ab");
+        registers.set('b', 7);
+        assert_eq!(registers.get('a'), 0);
+        assert_eq!(registers.values(), &[0, 7]);
+        assert_eq!(registers.slot('b'), 1);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut machine = countdown(3);
+        let outputs: Vec<Word> = std::iter::from_fn(|| machine.run_until_output()).collect();
+        assert_eq!(outputs, vec![3, 2, 1]);
+        assert!(machine.is_halted());
+        assert_eq!(machine.steps(), 3 * 3 + 1);
+        assert_eq!(machine.step(), Step::Halted);
+    }
+
+    #[test]
+    fn test_step_limit_and_trace() {
+        let mut machine = countdown(1000);
+        machine.set_step_limit(Some(10));
+        let mut trace = vec![];
+        assert_eq!(
+            machine.run_traced(|m| trace.push((m.pos(), m.get('n')))),
+            Step::OutOfSteps
+        );
+        assert_eq!(machine.steps(), 10);
+        assert_eq!(trace.len(), 11);
+        assert_eq!(&trace[..4], &[(0, 1000), (1, 1000), (2, 999), (0, 999)]);
+    }
+
+    #[test]
+    fn test_state() {
+        let mut machine = countdown(0);
+        machine.set('n', 5);
+        let start = machine.state();
+        assert_eq!(countdown(5).state(), start);
+        machine.step();
+        assert_ne!(machine.state(), start);
+        assert!(!machine.is_in_state(&start));
+        machine.advance(-1, 0);
+        assert_eq!(machine.state(), start);
+        assert!(machine.is_in_state(&start));
+    }
+}